@@ -0,0 +1,275 @@
+use crate::error::AppError;
+use axum::{
+    http::{header::CONTENT_TYPE, HeaderValue},
+    response::{IntoResponse, Response},
+};
+use bytes::Bytes;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Whether a response came from the service cache, reported in `X-Cache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CacheStatus {
+    Hit,
+    Miss,
+    Stale,
+}
+
+impl CacheStatus {
+    fn as_str(&self) -> &'static str {
+        match self {
+            CacheStatus::Hit => "HIT",
+            CacheStatus::Miss => "MISS",
+            CacheStatus::Stale => "STALE",
+        }
+    }
+}
+
+/// A serialized JSON body along with how it was obtained.
+#[derive(Debug)]
+pub struct CachedJson {
+    pub body: Bytes,
+    pub status: CacheStatus,
+}
+
+impl IntoResponse for CachedJson {
+    fn into_response(self) -> Response {
+        let mut response = self.body.into_response();
+        let headers = response.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        headers.insert("x-cache", HeaderValue::from_static(self.status.as_str()));
+        response
+    }
+}
+
+struct Entry {
+    body: Bytes,
+    stored_at: Instant,
+}
+
+/// A TTL cache for serialized responses in front of `GithubAPI`.
+///
+/// Entries younger than `ttl` are served as hits. Entries younger than
+/// `ttl + stale_ttl` are served as stale while a background task refreshes
+/// them. Concurrent misses for the same key wait for a single upstream call.
+pub struct ServiceCache {
+    ttl: Duration,
+    stale_ttl: Duration,
+    entries: Mutex<HashMap<String, Entry>>,
+    in_flight: Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>,
+}
+
+impl ServiceCache {
+    pub fn new(ttl: Duration, stale_ttl: Duration) -> ServiceCache {
+        ServiceCache {
+            ttl,
+            stale_ttl,
+            entries: Mutex::default(),
+            in_flight: Mutex::default(),
+        }
+    }
+
+    fn lookup(&self, key: &str) -> Option<CachedJson> {
+        let entries = self.entries.lock().unwrap();
+        let entry = entries.get(key)?;
+        let age = entry.stored_at.elapsed();
+        let status = if age < self.ttl {
+            CacheStatus::Hit
+        } else if age < self.ttl + self.stale_ttl {
+            CacheStatus::Stale
+        } else {
+            return None;
+        };
+        Some(CachedJson {
+            body: entry.body.clone(),
+            status,
+        })
+    }
+
+    fn store(&self, key: String, body: Bytes) {
+        let mut entries = self.entries.lock().unwrap();
+        let max_age = self.ttl + self.stale_ttl;
+        entries.retain(|_, entry| entry.stored_at.elapsed() < max_age);
+        entries.insert(
+            key,
+            Entry {
+                body,
+                stored_at: Instant::now(),
+            },
+        );
+        drop(entries);
+        // Locks only matter while someone holds them.
+        self.in_flight
+            .lock()
+            .unwrap()
+            .retain(|_, lock| Arc::strong_count(lock) > 1);
+    }
+
+    fn key_lock(&self, key: &str) -> Arc<tokio::sync::Mutex<()>> {
+        self.in_flight
+            .lock()
+            .unwrap()
+            .entry(key.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Removes the entry for `key`, or every entry starting with `prefix`.
+    /// Returns the number of entries removed.
+    pub fn purge(&self, key: Option<&str>, prefix: Option<&str>) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let before = entries.len();
+        match (key, prefix) {
+            (Some(key), _) => {
+                entries.remove(key);
+            }
+            (None, Some(prefix)) => entries.retain(|k, _| !k.starts_with(prefix)),
+            (None, None) => entries.clear(),
+        }
+        before - entries.len()
+    }
+
+    /// Serves `key` from the cache, calling `fetch` on a miss and refreshing
+    /// stale entries in the background.
+    pub async fn get_or_fetch<T, F, Fut>(
+        self: &Arc<Self>,
+        key: String,
+        fetch: F,
+    ) -> Result<CachedJson, AppError>
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, AppError>> + Send + 'static,
+    {
+        match self.lookup(&key) {
+            Some(cached) if cached.status == CacheStatus::Hit => return Ok(cached),
+            Some(cached) => {
+                self.revalidate(key, fetch);
+                return Ok(cached);
+            }
+            None => {}
+        }
+        let lock = self.key_lock(&key);
+        let _guard = lock.lock().await;
+        // Another request may have filled the entry while we waited.
+        if let Some(cached) = self.lookup(&key) {
+            return Ok(cached);
+        }
+        let body = encode(&fetch().await?);
+        self.store(key, body.clone());
+        Ok(CachedJson {
+            body,
+            status: CacheStatus::Miss,
+        })
+    }
+
+    fn revalidate<T, F, Fut>(self: &Arc<Self>, key: String, fetch: F)
+    where
+        T: Serialize,
+        F: FnOnce() -> Fut + Send + 'static,
+        Fut: Future<Output = Result<T, AppError>> + Send + 'static,
+    {
+        let guard = match self.key_lock(&key).try_lock_owned() {
+            Ok(guard) => guard,
+            // A refresh or miss for this key is already in flight.
+            Err(_) => return,
+        };
+        let cache = self.clone();
+        tokio::spawn(async move {
+            let _guard = guard;
+            match fetch().await {
+                Ok(value) => cache.store(key, encode(&value)),
+                Err(err) => tracing::warn!("Revalidating {} failed: {:?}", key, err),
+            }
+        });
+    }
+}
+
+fn encode<T: Serialize>(value: &T) -> Bytes {
+    serde_json::to_vec(value)
+        .expect("response types always serialize to JSON")
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CacheStatus, ServiceCache};
+    use crate::error::AppError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    fn counting_fetch(
+        calls: &Arc<AtomicUsize>,
+    ) -> impl FnOnce() -> futures::future::BoxFuture<'static, Result<usize, AppError>> {
+        let calls = calls.clone();
+        move || {
+            Box::pin(async move {
+                tokio::time::sleep(Duration::from_millis(20)).await;
+                Ok(calls.fetch_add(1, Ordering::SeqCst) + 1)
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_misses_are_coalesced() {
+        let cache = Arc::new(ServiceCache::new(
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        ));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let requests =
+            (0..10).map(|_| cache.get_or_fetch("/repos/a/b".into(), counting_fetch(&calls)));
+        let results = futures::future::join_all(requests).await;
+        assert_eq!(1, calls.load(Ordering::SeqCst));
+        let misses = results
+            .iter()
+            .filter(|r| r.as_ref().unwrap().status == CacheStatus::Miss)
+            .count();
+        assert_eq!(1, misses);
+    }
+
+    #[tokio::test]
+    async fn test_stale_entries_are_revalidated() {
+        let cache = Arc::new(ServiceCache::new(Duration::ZERO, Duration::from_secs(60)));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let first = cache
+            .get_or_fetch("/repos/a/b".into(), counting_fetch(&calls))
+            .await
+            .unwrap();
+        assert_eq!(CacheStatus::Miss, first.status);
+        let second = cache
+            .get_or_fetch("/repos/a/b".into(), counting_fetch(&calls))
+            .await
+            .unwrap();
+        assert_eq!(CacheStatus::Stale, second.status);
+        assert_eq!("1", second.body);
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(2, calls.load(Ordering::SeqCst));
+        let third = cache
+            .get_or_fetch("/repos/a/b".into(), counting_fetch(&calls))
+            .await
+            .unwrap();
+        assert_eq!("2", third.body);
+    }
+
+    #[tokio::test]
+    async fn test_purge_by_prefix() {
+        let cache = Arc::new(ServiceCache::new(
+            Duration::from_secs(60),
+            Duration::from_secs(60),
+        ));
+        let calls = Arc::new(AtomicUsize::new(0));
+        for key in ["/repos/a/b", "/repos/a/c", "/orgs/a/repos"] {
+            cache
+                .get_or_fetch(key.into(), counting_fetch(&calls))
+                .await
+                .unwrap();
+        }
+        assert_eq!(2, cache.purge(None, Some("/repos/a/")));
+        assert_eq!(1, cache.purge(None, None));
+    }
+}
@@ -0,0 +1,572 @@
+use crate::github::{
+    enterprise_urls, AppCredentials, ClientIdentity, ClientOptions, Credentials, RetryPolicy,
+};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::Url;
+use serde::Deserialize;
+use std::error::Error;
+use std::fmt::{self, Debug, Display};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Read when `GH_SERVICE_CONFIG` is not set, if it exists.
+static DEFAULT_CONFIG_PATH: &str = "gh-service.toml";
+static ENV_PREFIX: &str = "GH_SERVICE_";
+
+#[derive(Debug)]
+pub enum ConfigError {
+    ReadFailed,
+    ParseFailed,
+    InvalidEnvVar(String),
+    Invalid(String),
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ReadFailed => write!(f, "Reading the config file failed"),
+            Self::ParseFailed => write!(f, "Parsing the config file failed"),
+            Self::InvalidEnvVar(name) => write!(f, "Environment variable {} is invalid", name),
+            Self::Invalid(msg) => write!(f, "Invalid configuration - {}", msg),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// A string that is never printed, for tokens and keys.
+#[derive(Clone, Deserialize, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct Secret(String);
+
+impl Secret {
+    pub fn new(value: String) -> Secret {
+        Secret(value)
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Secret(***)")
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub github: GithubConfig,
+    pub cache: CacheConfig,
+    pub log: LogConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind: SocketAddr,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct GithubConfig {
+    pub base_url: String,
+    /// Root URL of a GitHub Enterprise Server instance. When set, the API
+    /// and upload URLs are derived from it and `base_url` is ignored.
+    pub enterprise_url: Option<String>,
+    pub upload_url: Option<String>,
+    /// PEM files with extra root certificates to trust.
+    pub ca_certs: Vec<PathBuf>,
+    pub client_cert: Option<ClientCertConfig>,
+    pub tokens: Vec<Secret>,
+    /// Authenticate as a GitHub App instead of with `tokens`.
+    pub app: Option<AppConfig>,
+    pub request_timeout_secs: u64,
+    pub connect_timeout_secs: u64,
+    pub max_rate_limit_wait_secs: u64,
+    pub retry: RetryConfig,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AppConfig {
+    pub app_id: u64,
+    pub private_key_path: PathBuf,
+    /// Installation used for requests that are not scoped to an owner.
+    pub installation_id: Option<u64>,
+}
+
+/// A PKCS#12 archive holding the client certificate and its key.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ClientCertConfig {
+    pub path: PathBuf,
+    pub password: Secret,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub base_delay_ms: u64,
+    pub max_delay_ms: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    /// Entries kept for conditional (ETag) requests to GitHub; 0 disables it.
+    pub conditional_capacity: usize,
+    pub ttl_secs: u64,
+    pub stale_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    /// A `tracing_subscriber::EnvFilter` directive; `RUST_LOG` wins if set.
+    pub filter: String,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        ServerConfig {
+            bind: ([127, 0, 0, 1], 3000).into(),
+        }
+    }
+}
+
+impl Default for GithubConfig {
+    fn default() -> Self {
+        GithubConfig {
+            base_url: "https://api.github.com".into(),
+            enterprise_url: None,
+            upload_url: None,
+            ca_certs: Vec::new(),
+            client_cert: None,
+            app: None,
+            tokens: Vec::new(),
+            request_timeout_secs: 30,
+            connect_timeout_secs: 10,
+            max_rate_limit_wait_secs: 5,
+            retry: RetryConfig::default(),
+        }
+    }
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        let policy = RetryPolicy::default();
+        RetryConfig {
+            max_attempts: policy.max_attempts,
+            base_delay_ms: policy.base_delay.as_millis() as u64,
+            max_delay_ms: policy.max_delay.as_millis() as u64,
+        }
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        CacheConfig {
+            conditional_capacity: 1024,
+            ttl_secs: 60,
+            stale_secs: 300,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        LogConfig {
+            filter: "info".into(),
+        }
+    }
+}
+
+fn env_override<T, E>(env: &E, name: &str, target: &mut T) -> Result<(), ConfigError>
+where
+    T: FromStr,
+    T::Err: Error + Send + Sync + 'static,
+    E: Fn(&str) -> Option<String>,
+{
+    let var = format!("{}{}", ENV_PREFIX, name);
+    if let Some(value) = env(&var) {
+        *target = value
+            .parse()
+            .report()
+            .change_context(ConfigError::InvalidEnvVar(var))?;
+    }
+    Ok(())
+}
+
+impl Config {
+    /// Loads the config file named by `GH_SERVICE_CONFIG` (or
+    /// `gh-service.toml` if present), then applies environment overrides.
+    pub fn from_env() -> Result<Config, ConfigError> {
+        let env = |name: &str| std::env::var(name).ok();
+        let path = match env("GH_SERVICE_CONFIG") {
+            Some(path) => Some(PathBuf::from(path)),
+            None => Some(PathBuf::from(DEFAULT_CONFIG_PATH)).filter(|p| p.exists()),
+        };
+        Config::load(path.as_deref(), env)
+    }
+
+    pub fn load<E>(path: Option<&Path>, env: E) -> Result<Config, ConfigError>
+    where
+        E: Fn(&str) -> Option<String>,
+    {
+        let mut config = match path {
+            Some(path) => {
+                let raw = std::fs::read_to_string(path)
+                    .report()
+                    .change_context(ConfigError::ReadFailed)
+                    .attach_printable_lazy(|| format!("path: {}", path.display()))?;
+                Config::parse(&raw).attach_printable_lazy(|| format!("path: {}", path.display()))?
+            }
+            None => Config::default(),
+        };
+        config.apply_env(&env)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    pub fn parse(raw: &str) -> Result<Config, ConfigError> {
+        toml::from_str(raw)
+            .report()
+            .change_context(ConfigError::ParseFailed)
+    }
+
+    fn apply_env<E: Fn(&str) -> Option<String>>(&mut self, env: &E) -> Result<(), ConfigError> {
+        env_override(env, "BIND", &mut self.server.bind)?;
+        env_override(env, "GITHUB_BASE_URL", &mut self.github.base_url)?;
+        if let Some(url) = env("GH_SERVICE_GITHUB_ENTERPRISE_URL") {
+            self.github.enterprise_url = Some(url);
+        }
+        if let Some(url) = env("GH_SERVICE_GITHUB_UPLOAD_URL") {
+            self.github.upload_url = Some(url);
+        }
+        if let Some(paths) = env("GH_SERVICE_GITHUB_CA_CERTS") {
+            self.github.ca_certs = paths.split(',').map(PathBuf::from).collect();
+        }
+        env_override(
+            env,
+            "GITHUB_REQUEST_TIMEOUT_SECS",
+            &mut self.github.request_timeout_secs,
+        )?;
+        env_override(
+            env,
+            "GITHUB_CONNECT_TIMEOUT_SECS",
+            &mut self.github.connect_timeout_secs,
+        )?;
+        env_override(
+            env,
+            "GITHUB_MAX_RATE_LIMIT_WAIT_SECS",
+            &mut self.github.max_rate_limit_wait_secs,
+        )?;
+        env_override(
+            env,
+            "GITHUB_RETRY_MAX_ATTEMPTS",
+            &mut self.github.retry.max_attempts,
+        )?;
+        env_override(
+            env,
+            "GITHUB_RETRY_BASE_DELAY_MS",
+            &mut self.github.retry.base_delay_ms,
+        )?;
+        env_override(
+            env,
+            "GITHUB_RETRY_MAX_DELAY_MS",
+            &mut self.github.retry.max_delay_ms,
+        )?;
+        env_override(
+            env,
+            "CACHE_CONDITIONAL_CAPACITY",
+            &mut self.cache.conditional_capacity,
+        )?;
+        env_override(env, "CACHE_TTL_SECS", &mut self.cache.ttl_secs)?;
+        env_override(env, "CACHE_STALE_SECS", &mut self.cache.stale_secs)?;
+        env_override(env, "LOG", &mut self.log.filter)?;
+        if let Some(app_id) = env("GH_SERVICE_GITHUB_APP_ID") {
+            let app_id = app_id
+                .parse()
+                .report()
+                .change_context(ConfigError::InvalidEnvVar(
+                    "GH_SERVICE_GITHUB_APP_ID".into(),
+                ))?;
+            let key_path = env("GH_SERVICE_GITHUB_APP_PRIVATE_KEY_PATH")
+                .map(PathBuf::from)
+                .or_else(|| self.github.app.as_ref().map(|a| a.private_key_path.clone()))
+                .ok_or_else(|| {
+                    Report::new(ConfigError::InvalidEnvVar(
+                        "GH_SERVICE_GITHUB_APP_PRIVATE_KEY_PATH".into(),
+                    ))
+                    .attach_printable("required when GH_SERVICE_GITHUB_APP_ID is set")
+                })?;
+            self.github.app = Some(AppConfig {
+                app_id,
+                private_key_path: key_path,
+                installation_id: self.github.app.as_ref().and_then(|a| a.installation_id),
+            });
+        }
+        if let Some(app) = self.github.app.as_mut() {
+            let mut installation = app.installation_id.unwrap_or_default();
+            env_override(env, "GITHUB_APP_INSTALLATION_ID", &mut installation)?;
+            app.installation_id = Some(installation).filter(|id| *id != 0);
+        }
+        // GITHUB_TOKEN is honoured for compatibility with earlier releases.
+        let tokens = env("GH_SERVICE_GITHUB_TOKENS").or_else(|| env("GITHUB_TOKEN"));
+        if let Some(tokens) = tokens {
+            self.github.tokens = tokens
+                .split(',')
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(|t| Secret::new(t.into()))
+                .collect();
+        }
+        Ok(())
+    }
+
+    fn validate(&self) -> Result<(), ConfigError> {
+        let invalid = |msg: &str| Err(Report::new(ConfigError::Invalid(msg.into())));
+        if self.github.tokens.is_empty() && self.github.app.is_none() {
+            return invalid(
+                "no GitHub credentials configured; set github.tokens, \
+                 GH_SERVICE_GITHUB_TOKENS or github.app",
+            );
+        }
+        let urls = [
+            ("github.base_url", Some(&self.github.base_url)),
+            ("github.enterprise_url", self.github.enterprise_url.as_ref()),
+            ("github.upload_url", self.github.upload_url.as_ref()),
+        ];
+        for (name, url) in urls {
+            match url.map(|url| Url::parse(url)) {
+                None => {}
+                Some(Ok(url)) if url.scheme() == "http" || url.scheme() == "https" => {}
+                _ => return invalid(&format!("{} must be an http(s) URL", name)),
+            }
+        }
+        if self.github.request_timeout_secs == 0 || self.github.connect_timeout_secs == 0 {
+            return invalid("github timeouts must be greater than zero");
+        }
+        if self.github.retry.max_attempts == 0 {
+            return invalid("github.retry.max_attempts must be at least 1");
+        }
+        if self.github.retry.base_delay_ms > self.github.retry.max_delay_ms {
+            return invalid("github.retry.base_delay_ms must not exceed max_delay_ms");
+        }
+        Ok(())
+    }
+
+    /// The REST API URL, taking a GitHub Enterprise Server instance into
+    /// account.
+    pub fn api_url(&self) -> String {
+        match &self.github.enterprise_url {
+            Some(instance) => enterprise_urls(instance).0,
+            None => self.github.base_url.clone(),
+        }
+    }
+
+    /// The credentials to use: the GitHub App if one is configured,
+    /// otherwise every configured token.
+    pub fn credentials(&self) -> Result<Credentials, ConfigError> {
+        match &self.github.app {
+            Some(app) => {
+                let private_key = std::fs::read(&app.private_key_path)
+                    .report()
+                    .change_context(ConfigError::ReadFailed)
+                    .attach_printable_lazy(|| {
+                        format!("path: {}", app.private_key_path.display())
+                    })?;
+                Ok(Credentials::App(AppCredentials {
+                    app_id: app.app_id,
+                    private_key,
+                    default_installation: app.installation_id,
+                }))
+            }
+            None => Ok(Credentials::Tokens(
+                self.github
+                    .tokens
+                    .iter()
+                    .map(|token| token.expose().to_string())
+                    .collect(),
+            )),
+        }
+    }
+
+    /// Builds the HTTP client settings, reading any certificate files.
+    pub fn client_options(&self) -> Result<ClientOptions, ConfigError> {
+        let read = |path: &Path| {
+            std::fs::read(path)
+                .report()
+                .change_context(ConfigError::ReadFailed)
+                .attach_printable_lazy(|| format!("path: {}", path.display()))
+        };
+        let root_certificates = self
+            .github
+            .ca_certs
+            .iter()
+            .map(|path| read(path))
+            .collect::<Result<Vec<_>, _>>()?;
+        let client_identity = match &self.github.client_cert {
+            Some(cert) => Some(ClientIdentity {
+                pkcs12: read(&cert.path)?,
+                password: cert.password.expose().to_string(),
+            }),
+            None => None,
+        };
+        let upload_url = match (&self.github.upload_url, &self.github.enterprise_url) {
+            (Some(url), _) => Some(url.clone()),
+            (None, Some(instance)) => Some(enterprise_urls(instance).1),
+            (None, None) => None,
+        };
+        Ok(ClientOptions {
+            request_timeout: Some(Duration::from_secs(self.github.request_timeout_secs)),
+            connect_timeout: Some(Duration::from_secs(self.github.connect_timeout_secs)),
+            root_certificates,
+            client_identity,
+            upload_url,
+        })
+    }
+
+    pub fn retry_policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.github.retry.max_attempts,
+            base_delay: Duration::from_millis(self.github.retry.base_delay_ms),
+            max_delay: Duration::from_millis(self.github.retry.max_delay_ms),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Config, ConfigError};
+    use crate::github::Credentials;
+    use std::collections::HashMap;
+
+    fn env(vars: &[(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        move |name| vars.get(name).cloned()
+    }
+
+    #[test]
+    fn test_env_overrides_file() {
+        let dir = std::env::temp_dir().join(format!("gh-service-config-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("gh-service.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [server]
+            bind = "0.0.0.0:8080"
+
+            [github]
+            base_url = "https://github.example.com/api/v3"
+            tokens = ["from-file"]
+
+            [cache]
+            ttl_secs = 10
+            "#,
+        )
+        .unwrap();
+        let config = Config::load(
+            Some(&path),
+            env(&[
+                ("GH_SERVICE_CACHE_TTL_SECS", "30"),
+                ("GITHUB_TOKEN", "from-env"),
+            ]),
+        )
+        .unwrap();
+        assert_eq!("0.0.0.0:8080", config.server.bind.to_string());
+        assert_eq!("https://github.example.com/api/v3", config.github.base_url);
+        assert_eq!(30, config.cache.ttl_secs);
+        assert_eq!("from-env", config.github.tokens[0].expose());
+        assert_eq!(300, config.cache.stale_secs);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_invalid_configs_are_rejected() {
+        let err = Config::load(None, env(&[])).unwrap_err();
+        assert!(matches!(err.current_context(), ConfigError::Invalid(_)));
+        let err = Config::load(
+            None,
+            env(&[("GITHUB_TOKEN", "t"), ("GH_SERVICE_BIND", "localhost")]),
+        )
+        .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConfigError::InvalidEnvVar(name) if name == "GH_SERVICE_BIND"
+        ));
+        assert!(Config::parse("[server]\nport = 3000").is_err());
+    }
+
+    #[test]
+    fn test_enterprise_url() {
+        let config = Config::load(
+            None,
+            env(&[
+                ("GITHUB_TOKEN", "t"),
+                (
+                    "GH_SERVICE_GITHUB_ENTERPRISE_URL",
+                    "https://ghe.example.com/",
+                ),
+            ]),
+        )
+        .unwrap();
+        assert_eq!("https://ghe.example.com/api/v3", config.api_url());
+        let options = config.client_options().unwrap();
+        assert_eq!(
+            Some("https://ghe.example.com/api/uploads".to_string()),
+            options.upload_url
+        );
+        let config = Config::load(
+            None,
+            env(&[
+                ("GITHUB_TOKEN", "t"),
+                ("GH_SERVICE_GITHUB_CA_CERTS", "/nonexistent.pem"),
+            ]),
+        )
+        .unwrap();
+        let err = config.client_options().unwrap_err();
+        assert!(matches!(err.current_context(), ConfigError::ReadFailed));
+    }
+
+    #[test]
+    fn test_app_credentials_from_env() {
+        let config = Config::load(
+            None,
+            env(&[
+                ("GH_SERVICE_GITHUB_APP_ID", "1234"),
+                (
+                    "GH_SERVICE_GITHUB_APP_PRIVATE_KEY_PATH",
+                    concat!(env!("CARGO_MANIFEST_DIR"), "/src/github/test_app_key.pem"),
+                ),
+            ]),
+        )
+        .unwrap();
+        assert!(matches!(
+            config.credentials().unwrap(),
+            Credentials::App(app) if app.app_id == 1234 && app.default_installation.is_none()
+        ));
+        let err = Config::load(None, env(&[("GH_SERVICE_GITHUB_APP_ID", "1234")])).unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            ConfigError::InvalidEnvVar(_)
+        ));
+    }
+
+    #[test]
+    fn test_tokens_are_not_printed() {
+        let config = Config::load(None, env(&[("GITHUB_TOKEN", "ghp_supersecret")])).unwrap();
+        assert!(!format!("{:?}", config).contains("ghp_supersecret"));
+    }
+}
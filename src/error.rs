@@ -0,0 +1,126 @@
+use crate::github::GHAPIError;
+use axum::{
+    http::{
+        header::{CONTENT_RANGE, RETRY_AFTER},
+        HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use error_stack::Report;
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Wraps a `GHAPIError` report so handlers can return it directly. It is
+/// rendered as `{ "error": { "code", "message", "upstream_status" } }`, plus
+/// GitHub's `documentation_url` when it sent one.
+#[derive(Debug)]
+pub enum AppError {
+    Upstream(Report<GHAPIError>),
+    /// The request to this service itself was malformed.
+    BadRequest(String),
+}
+
+impl From<Report<GHAPIError>> for AppError {
+    fn from(report: Report<GHAPIError>) -> Self {
+        AppError::Upstream(report)
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope {
+    error: ErrorBody,
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    code: &'static str,
+    message: String,
+    upstream_status: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    documentation_url: Option<String>,
+}
+
+fn status_and_code(err: &GHAPIError) -> (StatusCode, &'static str) {
+    match err {
+        GHAPIError::ClientCreationFailed => (StatusCode::INTERNAL_SERVER_ERROR, "internal"),
+        GHAPIError::RequestFailed => (StatusCode::BAD_GATEWAY, "upstream_unreachable"),
+        GHAPIError::FailedToDeserialize => (StatusCode::BAD_GATEWAY, "bad_upstream_response"),
+        GHAPIError::AuthenticationFailed => {
+            (StatusCode::INTERNAL_SERVER_ERROR, "authentication_failed")
+        }
+        GHAPIError::RateLimited { .. } => (StatusCode::TOO_MANY_REQUESTS, "rate_limited"),
+        GHAPIError::NotMergeable(_) => (StatusCode::METHOD_NOT_ALLOWED, "not_mergeable"),
+        GHAPIError::Conflict(_) => (StatusCode::CONFLICT, "conflict"),
+        GHAPIError::ResponseUnsuccessful(err) => match err.status {
+            StatusCode::NOT_FOUND => (StatusCode::NOT_FOUND, "not_found"),
+            StatusCode::UNAUTHORIZED => (StatusCode::BAD_GATEWAY, "unauthorized"),
+            StatusCode::FORBIDDEN => (StatusCode::FORBIDDEN, "forbidden"),
+            StatusCode::UNPROCESSABLE_ENTITY => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "validation_failed")
+            }
+            StatusCode::GONE => (StatusCode::GONE, "gone"),
+            StatusCode::RANGE_NOT_SATISFIABLE => {
+                (StatusCode::RANGE_NOT_SATISFIABLE, "range_not_satisfiable")
+            }
+            s if s.is_server_error() => (StatusCode::BAD_GATEWAY, "upstream_unavailable"),
+            _ => (StatusCode::BAD_GATEWAY, "upstream_error"),
+        },
+    }
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        let report = match self {
+            AppError::Upstream(report) => report,
+            AppError::BadRequest(message) => {
+                let body = ErrorEnvelope {
+                    error: ErrorBody {
+                        code: "bad_request",
+                        message,
+                        upstream_status: None,
+                        documentation_url: None,
+                    },
+                };
+                return (StatusCode::BAD_REQUEST, Json(body)).into_response();
+            }
+        };
+        tracing::error!("{:?}", report);
+        let context = report.current_context();
+        let (status, code) = status_and_code(context);
+        let (message, upstream_status, documentation_url) = match context.upstream() {
+            Some(err) => (
+                err.message.clone(),
+                Some(err.status.as_u16()),
+                err.documentation_url.clone(),
+            ),
+            None => (context.to_string(), None, None),
+        };
+        let body = ErrorEnvelope {
+            error: ErrorBody {
+                code,
+                message,
+                upstream_status,
+                documentation_url,
+            },
+        };
+        let mut response = (status, Json(body)).into_response();
+        // Lets a client resuming a download learn the resource's size.
+        let content_range = context
+            .upstream()
+            .and_then(|err| err.content_range.as_deref())
+            .and_then(|range| HeaderValue::from_str(range).ok());
+        if let (StatusCode::RANGE_NOT_SATISFIABLE, Some(range)) = (status, content_range) {
+            response.headers_mut().insert(CONTENT_RANGE, range);
+        }
+        if let GHAPIError::RateLimited { reset_at } = context {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or_default();
+            let retry_after = HeaderValue::from(reset_at.saturating_sub(now));
+            response.headers_mut().insert(RETRY_AFTER, retry_after);
+        }
+        response
+    }
+}
@@ -0,0 +1,336 @@
+use super::{models::User, refine_status, with_query, GHAPIError, GithubAPI};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Workflow {
+    pub id: u64,
+    pub node_id: String,
+    pub name: String,
+    /// The workflow file, e.g. `.github/workflows/ci.yml`.
+    pub path: String,
+    /// `active`, or why the workflow is disabled.
+    pub state: String,
+    pub html_url: String,
+    pub badge_url: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RunStatus {
+    Requested,
+    Queued,
+    Pending,
+    Waiting,
+    InProgress,
+    Completed,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Conclusion {
+    Success,
+    Failure,
+    Neutral,
+    Cancelled,
+    Skipped,
+    TimedOut,
+    ActionRequired,
+    Stale,
+    StartupFailure,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkflowRun {
+    pub id: u64,
+    pub node_id: String,
+    pub name: Option<String>,
+    pub workflow_id: u64,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+    pub run_number: u64,
+    #[serde(default = "first_attempt")]
+    pub run_attempt: u32,
+    /// What triggered the run, e.g. `push` or `workflow_dispatch`.
+    pub event: String,
+    pub status: Option<RunStatus>,
+    /// Set once the run has completed.
+    pub conclusion: Option<Conclusion>,
+    pub actor: Option<User>,
+    pub html_url: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub run_started_at: Option<DateTime<Utc>>,
+}
+
+fn first_attempt() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Step {
+    pub number: u32,
+    pub name: String,
+    pub status: RunStatus,
+    pub conclusion: Option<Conclusion>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub completed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Job {
+    pub id: u64,
+    pub run_id: u64,
+    pub node_id: String,
+    pub name: String,
+    pub head_sha: String,
+    pub status: RunStatus,
+    pub conclusion: Option<Conclusion>,
+    pub started_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub html_url: Option<String>,
+    pub runner_name: Option<String>,
+    #[serde(default)]
+    pub steps: Vec<Step>,
+}
+
+/// Filters for listing workflow runs.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RunFilter {
+    pub branch: Option<String>,
+    /// A triggering event such as `push` or `pull_request`.
+    pub event: Option<String>,
+    /// A status such as `in_progress`, or a conclusion such as `failure`.
+    pub status: Option<String>,
+    /// The login of the user who triggered the run.
+    pub actor: Option<String>,
+}
+
+/// Triggers a workflow that has a `workflow_dispatch` trigger.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WorkflowDispatch {
+    /// The branch or tag to run the workflow from.
+    #[serde(rename = "ref")]
+    pub reference: String,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub inputs: HashMap<String, serde_json::Value>,
+}
+
+impl GithubAPI {
+    pub async fn list_workflows(
+        &self,
+        owner: &str,
+        repo: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Workflow>, GHAPIError> {
+        let path = format!("repos/{}/{}/actions/workflows", owner, repo);
+        self.collect_wrapped(&path, "workflows", per_page, limit)
+            .await
+    }
+
+    /// Lists the runs of one workflow, given by ID or file name, or of all
+    /// workflows when `workflow` is `None`.
+    pub async fn list_workflow_runs(
+        &self,
+        owner: &str,
+        repo: &str,
+        workflow: Option<&str>,
+        filter: &RunFilter,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<WorkflowRun>, GHAPIError> {
+        let path = match workflow {
+            Some(workflow) => format!(
+                "repos/{}/{}/actions/workflows/{}/runs",
+                owner, repo, workflow
+            ),
+            None => format!("repos/{}/{}/actions/runs", owner, repo),
+        };
+        let path = with_query(&path, filter)?;
+        self.collect_wrapped(&path, "workflow_runs", per_page, limit)
+            .await
+    }
+
+    pub async fn get_workflow_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<WorkflowRun, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/actions/runs/{}", owner, repo, run_id))
+            .await
+    }
+
+    /// The jobs of a run's latest attempt, with their steps.
+    pub async fn list_run_jobs(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Job>, GHAPIError> {
+        let path = format!("repos/{}/{}/actions/runs/{}/jobs", owner, repo, run_id);
+        self.collect_wrapped(&path, "jobs", per_page, limit).await
+    }
+
+    /// Starts `workflow` (an ID or file name). GitHub does not say which
+    /// run it started.
+    pub async fn dispatch_workflow(
+        &self,
+        owner: &str,
+        repo: &str,
+        workflow: &str,
+        dispatch: &WorkflowDispatch,
+    ) -> Result<(), GHAPIError> {
+        let path = format!(
+            "repos/{}/{}/actions/workflows/{}/dispatches",
+            owner, repo, workflow
+        );
+        self.send(self.client.post(self.url(&path)).json(dispatch))
+            .await?;
+        Ok(())
+    }
+
+    /// Reruns every job of a run, or only the failed ones.
+    pub async fn rerun_workflow_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+        failed_only: bool,
+    ) -> Result<(), GHAPIError> {
+        let action = if failed_only {
+            "rerun-failed-jobs"
+        } else {
+            "rerun"
+        };
+        let path = format!(
+            "repos/{}/{}/actions/runs/{}/{}",
+            owner, repo, run_id, action
+        );
+        self.send(self.client.post(self.url(&path))).await?;
+        Ok(())
+    }
+
+    /// Cancels a run. A run that has already completed fails with
+    /// [`GHAPIError::Conflict`].
+    pub async fn cancel_workflow_run(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<(), GHAPIError> {
+        let path = format!("repos/{}/{}/actions/runs/{}/cancel", owner, repo, run_id);
+        self.send(self.client.post(self.url(&path)))
+            .await
+            .map_err(|report| refine_status(report, StatusCode::CONFLICT, GHAPIError::Conflict))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Conclusion, RunFilter, RunStatus, WorkflowDispatch};
+    use crate::github::{GHAPIError, GithubAPI};
+    use wiremock::{
+        matchers::{body_json, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static RUN: &str = include_str!("fixtures/workflow_run.json");
+    static JOB: &str = include_str!("fixtures/job.json");
+
+    fn wrapped(key: &str, item: &str) -> ResponseTemplate {
+        let item: serde_json::Value = serde_json::from_str(item).unwrap();
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "total_count": 1,
+            (key): [item]
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_list_runs_and_jobs() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/workflows/ci.yml/runs",
+            ))
+            .and(query_param("branch", "main"))
+            .and(query_param("status", "failure"))
+            .respond_with(wrapped("workflow_runs", RUN))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/runs/2810457233/jobs",
+            ))
+            .respond_with(wrapped("jobs", JOB))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let filter = RunFilter {
+            branch: Some("main".into()),
+            status: Some("failure".into()),
+            ..RunFilter::default()
+        };
+        let runs = gapi
+            .list_workflow_runs("tarkalabs", "ssh-signer", Some("ci.yml"), &filter, 30, 10)
+            .await
+            .unwrap();
+        assert_eq!(Some(RunStatus::Completed), runs[0].status);
+        assert_eq!(Some(Conclusion::Failure), runs[0].conclusion);
+        let jobs = gapi
+            .list_run_jobs("tarkalabs", "ssh-signer", runs[0].id, 30, 10)
+            .await
+            .unwrap();
+        assert_eq!(3, jobs[0].steps.len());
+        assert_eq!(Some(Conclusion::Failure), jobs[0].steps[1].conclusion);
+        assert!(jobs[0].steps[2].started_at.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_and_cancel() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/workflows/release.yml/dispatches",
+            ))
+            .and(body_json(serde_json::json!({
+                "ref": "main",
+                "inputs": { "version": "0.3.0" }
+            })))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/runs/2810457233/cancel",
+            ))
+            .respond_with(ResponseTemplate::new(409).set_body_json(serde_json::json!({
+                "message": "Cannot cancel a workflow run that is completed."
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let dispatch = WorkflowDispatch {
+            reference: "main".into(),
+            inputs: [("version".to_string(), serde_json::json!("0.3.0"))].into(),
+        };
+        gapi.dispatch_workflow("tarkalabs", "ssh-signer", "release.yml", &dispatch)
+            .await
+            .unwrap();
+        let err = gapi
+            .cancel_workflow_run("tarkalabs", "ssh-signer", 2810457233)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), GHAPIError::Conflict(_)));
+    }
+}
@@ -0,0 +1,172 @@
+use super::{stream::ByteStream, with_query, GHAPIError, GithubAPI};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use reqwest::header::RANGE;
+use serde::{Deserialize, Serialize};
+
+/// The run that uploaded an artifact.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ArtifactRun {
+    pub id: u64,
+    pub head_branch: Option<String>,
+    pub head_sha: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Artifact {
+    pub id: u64,
+    pub node_id: String,
+    pub name: String,
+    /// The size of the zip archive.
+    pub size_in_bytes: u64,
+    pub archive_download_url: String,
+    /// Expired artifacts are still listed but can no longer be downloaded.
+    pub expired: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub workflow_run: Option<ArtifactRun>,
+}
+
+#[derive(Debug, Serialize)]
+struct ArtifactQuery<'a> {
+    name: Option<&'a str>,
+}
+
+impl GithubAPI {
+    /// Lists a repository's artifacts, optionally only those called `name`.
+    pub async fn list_artifacts(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: Option<&str>,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Artifact>, GHAPIError> {
+        let path = with_query(
+            &format!("repos/{}/{}/actions/artifacts", owner, repo),
+            &ArtifactQuery { name },
+        )?;
+        self.collect_wrapped(&path, "artifacts", per_page, limit)
+            .await
+    }
+
+    pub async fn list_run_artifacts(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Artifact>, GHAPIError> {
+        let path = format!("repos/{}/{}/actions/runs/{}/artifacts", owner, repo, run_id);
+        self.collect_wrapped(&path, "artifacts", per_page, limit)
+            .await
+    }
+
+    pub async fn get_artifact(
+        &self,
+        owner: &str,
+        repo: &str,
+        artifact_id: u64,
+    ) -> Result<Artifact, GHAPIError> {
+        self.get_json(&format!(
+            "repos/{}/{}/actions/artifacts/{}",
+            owner, repo, artifact_id
+        ))
+        .await
+    }
+
+    /// Streams an artifact's zip archive, or only the byte `range` of it
+    /// (a `Range` header value such as `bytes=0-1023`). The archive is
+    /// served from wherever GitHub redirects to.
+    pub async fn download_artifact(
+        &self,
+        owner: &str,
+        repo: &str,
+        artifact_id: u64,
+        range: Option<&str>,
+    ) -> Result<ByteStream, GHAPIError> {
+        let url = self.url(&format!(
+            "repos/{}/{}/actions/artifacts/{}/zip",
+            owner, repo, artifact_id
+        ));
+        let mut req = self.client.get(url);
+        if let Some(range) = range {
+            req = req.header(RANGE, range);
+        }
+        Ok(ByteStream::from_response(self.send(req).await?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use futures::StreamExt;
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static ARTIFACT: &str = include_str!("fixtures/artifact.json");
+
+    #[tokio::test]
+    async fn test_list_artifacts_by_name() {
+        let server = MockServer::start().await;
+        let artifact: serde_json::Value = serde_json::from_str(ARTIFACT).unwrap();
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/actions/artifacts"))
+            .and(query_param("name", "test-reports"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "total_count": 1,
+                "artifacts": [artifact]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let artifacts = gapi
+            .list_artifacts("tarkalabs", "ssh-signer", Some("test-reports"), 30, 10)
+            .await
+            .unwrap();
+        assert_eq!(48213, artifacts[0].size_in_bytes);
+        assert_eq!(2810457233, artifacts[0].workflow_run.as_ref().unwrap().id);
+    }
+
+    #[tokio::test]
+    async fn test_download_range_follows_redirect() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+            ))
+            .respond_with(ResponseTemplate::new(302).insert_header(
+                "Location",
+                format!("{}/storage/test-reports.zip", server.uri()).as_str(),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/test-reports.zip"))
+            .and(header("Range", "bytes=0-3"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("Content-Range", "bytes 0-3/48213")
+                    .set_body_bytes(b"PK\x03\x04".to_vec()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let mut stream = gapi
+            .download_artifact("tarkalabs", "ssh-signer", 330147421, Some("bytes=0-3"))
+            .await
+            .unwrap();
+        assert_eq!(Some("bytes 0-3/48213"), stream.content_range.as_deref());
+        assert_eq!(Some(4), stream.content_length);
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.body.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(b"PK\x03\x04".to_vec(), body);
+    }
+}
@@ -0,0 +1,424 @@
+use super::{
+    cache::token_hash, token_pool::TokenPool, unsuccessful_response, GHAPIError, GithubAPI,
+};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use reqwest::{
+    header::{HeaderValue, AUTHORIZATION},
+    Method, StatusCode, Url,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Installation tokens are refreshed this long before GitHub expires them.
+const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 5;
+/// GitHub rejects app JWTs that live longer than ten minutes.
+const JWT_LIFETIME_SECS: i64 = 9 * 60;
+
+/// How `GithubAPI` authenticates its requests.
+pub enum Credentials {
+    /// A personal access token or any other static token.
+    Token(String),
+    /// Several static tokens, spread across requests by remaining budget.
+    Tokens(Vec<String>),
+    App(AppCredentials),
+}
+
+/// A GitHub App, which authenticates as one of its installations.
+pub struct AppCredentials {
+    pub app_id: u64,
+    /// The app's PEM encoded RSA private key.
+    pub private_key: Vec<u8>,
+    /// Used for requests that are not scoped to an owner, e.g. `/rate_limit`.
+    pub default_installation: Option<u64>,
+}
+
+pub(super) enum Auth {
+    Pool(TokenPool),
+    App(AppAuth),
+}
+
+/// The `Authorization` header for one request, and the hash identifying
+/// the credential whose rate limit it counts against.
+#[derive(Clone)]
+pub(super) struct Credential {
+    pub header: HeaderValue,
+    pub hash: u64,
+}
+
+pub(super) struct AppAuth {
+    app_id: u64,
+    key: EncodingKey,
+    default_installation: Option<u64>,
+    installations: Mutex<HashMap<String, u64>>,
+    tokens: Mutex<HashMap<u64, InstallationToken>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct InstallationToken {
+    token: String,
+    expires_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Installation {
+    id: u64,
+}
+
+#[derive(Debug, Serialize)]
+struct Claims {
+    iat: i64,
+    exp: i64,
+    iss: String,
+}
+
+impl Auth {
+    pub(super) fn new(credentials: Credentials) -> Result<Auth, GHAPIError> {
+        match credentials {
+            Credentials::Token(token) => Ok(Auth::Pool(TokenPool::new(vec![token])?)),
+            Credentials::Tokens(tokens) => Ok(Auth::Pool(TokenPool::new(tokens)?)),
+            Credentials::App(app) => {
+                let key = EncodingKey::from_rsa_pem(&app.private_key)
+                    .report()
+                    .change_context(GHAPIError::ClientCreationFailed)
+                    .attach_printable("invalid GitHub App private key")?;
+                Ok(Auth::App(AppAuth {
+                    app_id: app.app_id,
+                    key,
+                    default_installation: app.default_installation,
+                    installations: Mutex::default(),
+                    tokens: Mutex::default(),
+                }))
+            }
+        }
+    }
+}
+
+impl AppAuth {
+    /// Signs a short-lived RS256 JWT identifying the app itself.
+    fn jwt(&self) -> Result<HeaderValue, GHAPIError> {
+        let now = Utc::now().timestamp();
+        let claims = Claims {
+            // Backdated to allow for clock drift, as GitHub recommends.
+            iat: now - 60,
+            exp: now + JWT_LIFETIME_SECS,
+            iss: self.app_id.to_string(),
+        };
+        let jwt = jsonwebtoken::encode(&Header::new(Algorithm::RS256), &claims, &self.key)
+            .report()
+            .change_context(GHAPIError::AuthenticationFailed)?;
+        let mut header = HeaderValue::from_str(&format!("Bearer {}", jwt))
+            .report()
+            .change_context(GHAPIError::AuthenticationFailed)?;
+        header.set_sensitive(true);
+        Ok(header)
+    }
+
+    fn cached_token(&self, installation: u64) -> Option<String> {
+        let margin = ChronoDuration::minutes(TOKEN_REFRESH_MARGIN_MINUTES);
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(&installation)
+            .filter(|t| t.expires_at - margin > Utc::now())
+            .map(|t| t.token.clone())
+    }
+}
+
+/// The account a request is scoped to, from `/repos/{owner}/..`,
+/// `/orgs/{org}/..` or `/users/{user}/..`.
+fn owner_from_path(path: &str) -> Option<&str> {
+    let mut segments = path.trim_start_matches('/').split('/');
+    match (segments.next(), segments.next()) {
+        (Some("repos" | "orgs" | "users"), Some(owner)) if !owner.is_empty() => Some(owner),
+        _ => None,
+    }
+}
+
+impl GithubAPI {
+    /// The request path relative to the API or uploads root.
+    fn api_path<'a>(&self, url: &'a Url) -> &'a str {
+        let url = url.as_str();
+        let url = url.split(['?', '#']).next().unwrap_or(url);
+        url.strip_prefix(self.base_url.as_str())
+            .or_else(|| url.strip_prefix(self.upload_url.as_str()))
+            .unwrap_or(url)
+    }
+
+    /// The credential for a request to `url`. Token pools pick a token by
+    /// remaining budget. GitHub Apps use the installation token of the
+    /// request's owner, fetching and caching it as needed, and their JWT
+    /// for `/app` endpoints.
+    pub(super) async fn authorization(&self, url: &Url) -> Result<Credential, GHAPIError> {
+        let app = match &self.auth {
+            Auth::Pool(pool) => {
+                let (header, hash) = self.select_token(pool)?;
+                return Ok(Credential { header, hash });
+            }
+            Auth::App(app) => app,
+        };
+        let app_credential = |header| Credential {
+            header,
+            hash: token_hash(&format!("app:{}", app.app_id)),
+        };
+        let path = self.api_path(url);
+        if path.trim_start_matches('/').starts_with("app") {
+            return app.jwt().map(app_credential);
+        }
+        let installation = match owner_from_path(path) {
+            Some(owner) => self.installation_for(app, owner).await?,
+            None => match app.default_installation {
+                Some(installation) => installation,
+                None => return app.jwt().map(app_credential),
+            },
+        };
+        let token = self.installation_token(app, installation).await?;
+        let mut header = HeaderValue::from_str(&format!("token {}", token))
+            .report()
+            .change_context(GHAPIError::AuthenticationFailed)?;
+        header.set_sensitive(true);
+        Ok(Credential {
+            header,
+            hash: token_hash(&format!("app:{}:{}", app.app_id, installation)),
+        })
+    }
+
+    /// The credential to read every page of a listing starting at `url`
+    /// with. GitHub Apps need one, as the `next` links of some listings name
+    /// the owner by ID (`/organizations/{id}/..`), which does not tell the
+    /// installation. Token pools keep choosing a token per page.
+    pub(super) async fn listing_credential(
+        &self,
+        url: &str,
+    ) -> Result<Option<Credential>, GHAPIError> {
+        match &self.auth {
+            Auth::Pool(_) => Ok(None),
+            Auth::App(_) => {
+                let url = Url::parse(url)
+                    .report()
+                    .change_context(GHAPIError::RequestFailed)?;
+                Ok(Some(self.authorization(&url).await?))
+            }
+        }
+    }
+
+    /// Takes a pooled token out of rotation after GitHub rejected it.
+    /// Returns whether it was, leaving another token to retry with.
+    pub(super) fn reject_credential(&self, hash: u64) -> bool {
+        match &self.auth {
+            Auth::Pool(pool) => pool.reject(hash),
+            Auth::App(_) => false,
+        }
+    }
+
+    /// Sends a request authenticated as the app itself. These bypass
+    /// `send` so that fetching a token never needs a token.
+    async fn app_request<T: DeserializeOwned>(
+        &self,
+        app: &AppAuth,
+        method: Method,
+        path: &str,
+    ) -> Result<T, GHAPIError> {
+        let resp = self
+            .client
+            .request(method, self.url(path))
+            .header(AUTHORIZATION, app.jwt()?)
+            .send()
+            .await
+            .report()
+            .change_context(GHAPIError::RequestFailed)?;
+        if !resp.status().is_success() {
+            return Err(unsuccessful_response(resp).await);
+        }
+        resp.json::<T>()
+            .await
+            .report()
+            .change_context(GHAPIError::FailedToDeserialize)
+    }
+
+    async fn installation_for(&self, app: &AppAuth, owner: &str) -> Result<u64, GHAPIError> {
+        let key = owner.to_lowercase();
+        if let Some(id) = app.installations.lock().unwrap().get(&key) {
+            return Ok(*id);
+        }
+        let org_path = format!("orgs/{}/installation", owner);
+        let installation = match self
+            .app_request::<Installation>(app, Method::GET, &org_path)
+            .await
+        {
+            Ok(installation) => installation,
+            Err(err) if is_not_found(&err) => {
+                let user_path = format!("users/{}/installation", owner);
+                self.app_request::<Installation>(app, Method::GET, &user_path)
+                    .await
+                    .change_context(GHAPIError::AuthenticationFailed)
+                    .attach_printable_lazy(|| format!("app is not installed for {}", owner))?
+            }
+            Err(err) => return Err(err.change_context(GHAPIError::AuthenticationFailed)),
+        };
+        app.installations
+            .lock()
+            .unwrap()
+            .insert(key, installation.id);
+        Ok(installation.id)
+    }
+
+    async fn installation_token(
+        &self,
+        app: &AppAuth,
+        installation: u64,
+    ) -> Result<String, GHAPIError> {
+        if let Some(token) = app.cached_token(installation) {
+            return Ok(token);
+        }
+        tracing::debug!("Fetching an access token for installation {}", installation);
+        let path = format!("app/installations/{}/access_tokens", installation);
+        let token: InstallationToken = self
+            .app_request(app, Method::POST, &path)
+            .await
+            .change_context(GHAPIError::AuthenticationFailed)?;
+        app.tokens
+            .lock()
+            .unwrap()
+            .insert(installation, token.clone());
+        Ok(token.token)
+    }
+}
+
+fn is_not_found(err: &Report<GHAPIError>) -> bool {
+    matches!(
+        err.current_context(),
+        GHAPIError::ResponseUnsuccessful(upstream) if upstream.status == StatusCode::NOT_FOUND
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{owner_from_path, AppCredentials, Credentials};
+    use crate::github::{ClientOptions, GithubAPI};
+    use chrono::{Duration, Utc};
+    use futures::TryStreamExt;
+    use wiremock::{
+        matchers::{header, header_regex, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
+    static TEST_KEY: &[u8] = include_bytes!("test_app_key.pem");
+
+    fn app_api(server: &MockServer) -> GithubAPI {
+        let credentials = Credentials::App(AppCredentials {
+            app_id: 1234,
+            private_key: TEST_KEY.to_vec(),
+            default_installation: None,
+        });
+        GithubAPI::with_options(credentials, Some(server.uri()), ClientOptions::default()).unwrap()
+    }
+
+    async fn mount_app(server: &MockServer, expires_in: Duration, exchanges: u64) {
+        Mock::given(method("GET"))
+            .and(path("/orgs/tarkalabs/installation"))
+            .and(header_regex("Authorization", "^Bearer "))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({ "id": 42 })))
+            .expect(1)
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/app/installations/42/access_tokens"))
+            .and(header_regex("Authorization", "^Bearer "))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "token": "ghs_installation",
+                "expires_at": (Utc::now() + expires_in).to_rfc3339(),
+            })))
+            .expect(exchanges)
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token ghs_installation"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_owner_from_path() {
+        assert_eq!(
+            Some("tarkalabs"),
+            owner_from_path("/repos/tarkalabs/ssh-signer")
+        );
+        assert_eq!(Some("tarkalabs"), owner_from_path("/orgs/tarkalabs/repos"));
+        assert_eq!(None, owner_from_path("/rate_limit"));
+    }
+
+    #[tokio::test]
+    async fn test_installation_tokens_are_cached() {
+        let server = MockServer::start().await;
+        mount_app(&server, Duration::hours(1), 1).await;
+        let gapi = app_api(&server);
+        for _ in 0..2 {
+            let resp = gapi
+                .get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+            assert_eq!("tarkalabs/ssh-signer", resp.full_name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_later_pages_use_the_installation_token() {
+        let server = MockServer::start().await;
+        mount_app(&server, Duration::hours(1), 1).await;
+        Mock::given(method("GET"))
+            .and(path("/orgs/tarkalabs/repos"))
+            .and(header("Authorization", "token ghs_installation"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "id": 1 }]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{}/organizations/7/repos?per_page=1&page=2>; rel="next""#,
+                            server.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/organizations/7/repos"))
+            .and(header("Authorization", "token ghs_installation"))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(serde_json::json!([{ "id": 2 }])),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = app_api(&server);
+        let repos: Vec<serde_json::Value> = gapi
+            .paginate("orgs/tarkalabs/repos", 1)
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(2, repos.len());
+    }
+
+    #[tokio::test]
+    async fn test_expiring_tokens_are_refreshed() {
+        let server = MockServer::start().await;
+        mount_app(&server, Duration::minutes(2), 2).await;
+        let gapi = app_api(&server);
+        for _ in 0..2 {
+            gapi.get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+        }
+    }
+}
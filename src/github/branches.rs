@@ -0,0 +1,334 @@
+use super::{commits::ObjectRef, git::Reference, GHAPIError, GithubAPI};
+use error_stack::Result;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+/// GitHub's message when a branch exists but has no protection. Missing
+/// branches and repositories, and tokens without admin rights, also get a
+/// 404 but with a different message.
+const NOT_PROTECTED: &str = "Branch not protected";
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Branch {
+    pub name: String,
+    /// The branch head.
+    pub commit: ObjectRef,
+    pub protected: bool,
+}
+
+/// A protection setting GitHub reports as `{ "enabled": bool }`.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+pub struct Toggle {
+    pub enabled: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RequiredStatusChecks {
+    /// Whether the branch must be up to date with the base before merging.
+    pub strict: bool,
+    pub contexts: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RequiredReviews {
+    #[serde(default)]
+    pub dismiss_stale_reviews: bool,
+    #[serde(default)]
+    pub require_code_owner_reviews: bool,
+    #[serde(default)]
+    pub required_approving_review_count: u8,
+}
+
+/// Who may push to a protected branch, by login, team slug and app slug.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PushRestrictions {
+    pub users: Vec<String>,
+    pub teams: Vec<String>,
+    #[serde(default)]
+    pub apps: Vec<String>,
+}
+
+/// The protection rules of a branch. Rules that are off are `None`.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BranchProtection {
+    pub required_status_checks: Option<RequiredStatusChecks>,
+    pub enforce_admins: Option<Toggle>,
+    pub required_pull_request_reviews: Option<RequiredReviews>,
+    pub required_linear_history: Option<Toggle>,
+    pub allow_force_pushes: Option<Toggle>,
+    pub allow_deletions: Option<Toggle>,
+}
+
+/// Replaces every protection rule of a branch. `None` turns a rule off.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ProtectionUpdate {
+    pub required_status_checks: Option<RequiredStatusChecks>,
+    pub enforce_admins: Option<bool>,
+    pub required_pull_request_reviews: Option<RequiredReviews>,
+    pub restrictions: Option<PushRestrictions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub required_linear_history: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_force_pushes: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub allow_deletions: Option<bool>,
+}
+
+/// The protections a compliance report expects on default branches.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ProtectionPolicy {
+    /// Approving reviews required before merging; 0 requires none.
+    pub min_approvals: u8,
+    /// Status checks that must be required.
+    pub status_checks: Vec<String>,
+    /// Whether the rules must apply to administrators too.
+    pub enforce_admins: bool,
+}
+
+impl ProtectionPolicy {
+    /// Describes each way `protection` falls short of the policy. An
+    /// unprotected branch misses everything the policy asks for.
+    pub fn violations(&self, protection: Option<&BranchProtection>) -> Vec<String> {
+        let mut missing = Vec::new();
+        let approvals = protection
+            .and_then(|p| p.required_pull_request_reviews.as_ref())
+            .map_or(0, |reviews| reviews.required_approving_review_count);
+        if approvals < self.min_approvals {
+            missing.push(format!(
+                "requires {} approving reviews, expected at least {}",
+                approvals, self.min_approvals
+            ));
+        }
+        let contexts = protection
+            .and_then(|p| p.required_status_checks.as_ref())
+            .map(|checks| checks.contexts.as_slice())
+            .unwrap_or_default();
+        for check in &self.status_checks {
+            if !contexts.contains(check) {
+                missing.push(format!("status check {} is not required", check));
+            }
+        }
+        let enforce_admins = matches!(
+            protection.and_then(|p| p.enforce_admins),
+            Some(toggle) if toggle.enabled
+        );
+        if self.enforce_admins && !enforce_admins {
+            missing.push("rules are not enforced for administrators".into());
+        }
+        missing
+    }
+}
+
+impl GithubAPI {
+    pub async fn list_branches(
+        &self,
+        owner: &str,
+        repo: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Branch>, GHAPIError> {
+        self.collect_paginated(
+            &format!("repos/{}/{}/branches", owner, repo),
+            per_page,
+            limit,
+        )
+        .await
+    }
+
+    pub async fn get_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<Branch, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/branches/{}", owner, repo, branch))
+            .await
+    }
+
+    /// Creates `branch` pointing at `sha`.
+    pub async fn create_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        sha: &str,
+    ) -> Result<Reference, GHAPIError> {
+        self.create_ref(owner, repo, &format!("refs/heads/{}", branch), sha)
+            .await
+    }
+
+    pub async fn delete_branch(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<(), GHAPIError> {
+        let path = format!("repos/{}/{}/git/refs/heads/{}", owner, repo, branch);
+        self.send(self.client.delete(self.url(&path))).await?;
+        Ok(())
+    }
+
+    /// The branch's protection rules, or `None` when it is not protected.
+    /// Any other 404, e.g. for a missing branch, is an error.
+    pub async fn get_branch_protection(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+    ) -> Result<Option<BranchProtection>, GHAPIError> {
+        let path = format!("repos/{}/{}/branches/{}/protection", owner, repo, branch);
+        match self.get_json(&path).await {
+            Ok(protection) => Ok(Some(protection)),
+            Err(report) => match report.current_context().upstream() {
+                Some(err)
+                    if err.status == StatusCode::NOT_FOUND && err.message == NOT_PROTECTED =>
+                {
+                    Ok(None)
+                }
+                _ => Err(report),
+            },
+        }
+    }
+
+    pub async fn update_branch_protection(
+        &self,
+        owner: &str,
+        repo: &str,
+        branch: &str,
+        update: &ProtectionUpdate,
+    ) -> Result<BranchProtection, GHAPIError> {
+        let path = format!("repos/{}/{}/branches/{}/protection", owner, repo, branch);
+        self.send_json(Method::PUT, &path, update).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ProtectionPolicy, ProtectionUpdate, RequiredReviews};
+    use crate::github::GithubAPI;
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static PROTECTION: &str = include_str!("fixtures/branch_protection.json");
+
+    #[tokio::test]
+    async fn test_branch_protection() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/branches/main/protection"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(PROTECTION)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/branches/scratch/protection",
+            ))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Branch not protected"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let protection = gapi
+            .get_branch_protection("tarkalabs", "ssh-signer", "main")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            vec!["ci/build"],
+            protection.required_status_checks.unwrap().contexts
+        );
+        assert!(!protection.enforce_admins.unwrap().enabled);
+        assert!(gapi
+            .get_branch_protection("tarkalabs", "ssh-signer", "scratch")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_missing_branch_is_not_unprotected() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/branches/gone/protection"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Branch not found"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let err = gapi
+            .get_branch_protection("tarkalabs", "ssh-signer", "gone")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            404,
+            err.current_context().upstream().unwrap().status.as_u16()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_branch_protection_sends_every_rule() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/branches/main/protection"))
+            .and(body_json(serde_json::json!({
+                "required_status_checks": null,
+                "enforce_admins": true,
+                "required_pull_request_reviews": {
+                    "dismiss_stale_reviews": true,
+                    "require_code_owner_reviews": false,
+                    "required_approving_review_count": 2
+                },
+                "restrictions": null
+            })))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(PROTECTION)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let update = ProtectionUpdate {
+            enforce_admins: Some(true),
+            required_pull_request_reviews: Some(RequiredReviews {
+                dismiss_stale_reviews: true,
+                required_approving_review_count: 2,
+                ..RequiredReviews::default()
+            }),
+            ..ProtectionUpdate::default()
+        };
+        gapi.update_branch_protection("tarkalabs", "ssh-signer", "main", &update)
+            .await
+            .unwrap();
+    }
+
+    #[test]
+    fn test_policy_violations() {
+        let protection = serde_json::from_str(PROTECTION).unwrap();
+        let policy = ProtectionPolicy {
+            min_approvals: 2,
+            status_checks: vec!["ci/build".into(), "ci/lint".into()],
+            enforce_admins: true,
+        };
+        assert_eq!(
+            vec![
+                "requires 1 approving reviews, expected at least 2",
+                "status check ci/lint is not required",
+                "rules are not enforced for administrators",
+            ],
+            policy.violations(Some(&protection))
+        );
+        assert!(ProtectionPolicy::default()
+            .violations(Some(&protection))
+            .is_empty());
+        assert_eq!(4, policy.violations(None).len());
+    }
+}
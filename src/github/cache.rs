@@ -0,0 +1,304 @@
+use super::{auth::Credential, GHAPIError, GithubAPI};
+use bytes::Bytes;
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use lru::LruCache;
+use reqwest::{
+    header::{HeaderMap, ACCEPT, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED},
+    StatusCode,
+};
+use serde::de::DeserializeOwned;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Mutex;
+
+/// Identifies a cached response. Responses differ per token (private repos,
+/// permissions), so the token that sent the request is part of the key, but
+/// only as a hash.
+/// `media_type` is set when something other than JSON was requested.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CacheKey {
+    pub url: String,
+    pub token_hash: u64,
+    pub media_type: Option<&'static str>,
+}
+
+/// The headers and body of a successful GET.
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl CachedResponse {
+    fn validator(&self, name: reqwest::header::HeaderName) -> Option<&str> {
+        self.headers.get(name)?.to_str().ok()
+    }
+
+    pub fn etag(&self) -> Option<&str> {
+        self.validator(ETAG)
+    }
+
+    pub fn last_modified(&self) -> Option<&str> {
+        self.validator(LAST_MODIFIED)
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, GHAPIError> {
+        serde_json::from_slice(&self.body)
+            .report()
+            .change_context(GHAPIError::FailedToDeserialize)
+    }
+
+    /// The body as text. Diffs and patches may contain files in any
+    /// encoding, so invalid UTF-8 is replaced rather than rejected.
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+}
+
+/// Storage for conditional-request validators and bodies. Implementations
+/// must be safe to share between concurrent requests.
+pub trait ResponseCache: Send + Sync {
+    fn get(&self, key: &CacheKey) -> Option<CachedResponse>;
+    fn put(&self, key: CacheKey, response: CachedResponse);
+}
+
+/// An in-memory `ResponseCache` that evicts the least recently used entry
+/// once `capacity` is reached.
+pub struct InMemoryLruCache {
+    entries: Mutex<LruCache<CacheKey, CachedResponse>>,
+}
+
+impl InMemoryLruCache {
+    pub fn new(capacity: usize) -> InMemoryLruCache {
+        InMemoryLruCache {
+            entries: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+}
+
+impl ResponseCache for InMemoryLruCache {
+    fn get(&self, key: &CacheKey) -> Option<CachedResponse> {
+        self.entries.lock().unwrap().get(key).cloned()
+    }
+
+    fn put(&self, key: CacheKey, response: CachedResponse) {
+        self.entries.lock().unwrap().put(key, response);
+    }
+}
+
+pub(super) fn token_hash(token: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    token.hash(&mut hasher);
+    hasher.finish()
+}
+
+impl GithubAPI {
+    /// GETs `url`, revalidating any cached copy with `If-None-Match` or
+    /// `If-Modified-Since`. A 304 is answered from the cache and does not
+    /// count against the rate limit.
+    pub(super) async fn get_cached(&self, url: String) -> Result<CachedResponse, GHAPIError> {
+        self.get_cached_as(url, None).await
+    }
+
+    /// Like `get_cached`, but asks for `media_type` instead of JSON.
+    pub(super) async fn get_cached_as(
+        &self,
+        url: String,
+        media_type: Option<&'static str>,
+    ) -> Result<CachedResponse, GHAPIError> {
+        self.get_cached_with(url, media_type, None).await
+    }
+
+    /// Like `get_cached_as`, but sends with `credential` when one is given
+    /// instead of choosing one for `url`.
+    pub(super) async fn get_cached_with(
+        &self,
+        url: String,
+        media_type: Option<&'static str>,
+        credential: Option<Credential>,
+    ) -> Result<CachedResponse, GHAPIError> {
+        let cache = match &self.cache {
+            Some(cache) => cache,
+            None => return self.get_uncached(url, media_type, credential).await,
+        };
+        // The token is chosen up front so the key names the token that
+        // sends the request, not the credential set as a whole.
+        let credential = match credential {
+            Some(credential) => credential,
+            None => {
+                self.authorization(
+                    &reqwest::Url::parse(&url)
+                        .report()
+                        .change_context(GHAPIError::RequestFailed)?,
+                )
+                .await?
+            }
+        };
+        let mut key = CacheKey {
+            url: url.clone(),
+            token_hash: credential.hash,
+            media_type,
+        };
+        let cached = cache.get(&key);
+        let mut req = self.client.get(url);
+        if let Some(media_type) = media_type {
+            req = req.header(ACCEPT, media_type);
+        }
+        if let Some(cached) = &cached {
+            if let Some(etag) = cached.etag() {
+                req = req.header(IF_NONE_MATCH, etag);
+            } else if let Some(last_modified) = cached.last_modified() {
+                req = req.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+        // A retry may have switched tokens. A 304 still holds, as GitHub
+        // checked the validators against the token that sent them.
+        let (resp, sent_with) = self.send_as(req, Some(credential)).await?;
+        if resp.status() == StatusCode::NOT_MODIFIED {
+            tracing::debug!("Serving {} from the response cache", key.url);
+            return cached.ok_or_else(|| {
+                Report::new(GHAPIError::FailedToDeserialize)
+                    .attach_printable("304 Not Modified for a request that was not conditional")
+            });
+        }
+        let fresh = CachedResponse {
+            headers: resp.headers().clone(),
+            body: resp
+                .bytes()
+                .await
+                .report()
+                .change_context(GHAPIError::FailedToDeserialize)?,
+        };
+        if fresh.etag().is_some() || fresh.last_modified().is_some() {
+            key.token_hash = sent_with;
+            cache.put(key, fresh.clone());
+        }
+        Ok(fresh)
+    }
+
+    async fn get_uncached(
+        &self,
+        url: String,
+        media_type: Option<&'static str>,
+        credential: Option<Credential>,
+    ) -> Result<CachedResponse, GHAPIError> {
+        let mut req = self.client.get(url);
+        if let Some(media_type) = media_type {
+            req = req.header(ACCEPT, media_type);
+        }
+        let (resp, _) = self.send_as(req, credential).await?;
+        Ok(CachedResponse {
+            headers: resp.headers().clone(),
+            body: resp
+                .bytes()
+                .await
+                .report()
+                .change_context(GHAPIError::FailedToDeserialize)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::InMemoryLruCache;
+    use crate::github::{ClientOptions, Credentials, GithubAPI};
+    use std::sync::Arc;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
+
+    #[tokio::test]
+    async fn test_not_modified_is_served_from_cache() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("If-None-Match", r#""abc123""#))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json")
+                    .insert_header("ETag", r#""abc123""#),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri()))
+            .unwrap()
+            .with_response_cache(Arc::new(InMemoryLruCache::new(16)));
+        for _ in 0..2 {
+            let resp = gapi
+                .get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+            assert_eq!("tarkalabs/ssh-signer", resp.full_name);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cache_is_kept_per_pooled_token() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token second"))
+            .and(header("If-None-Match", r#""first-etag""#))
+            .respond_with(ResponseTemplate::new(304))
+            .expect(0)
+            .mount(&server)
+            .await;
+        for token in ["first", "second"] {
+            Mock::given(method("GET"))
+                .and(path("/repos/tarkalabs/ssh-signer"))
+                .and(header("Authorization", format!("token {}", token).as_str()))
+                .respond_with(
+                    ResponseTemplate::new(200)
+                        .set_body_string(MOCK_BODY)
+                        .insert_header("Content-Type", "application/json")
+                        .insert_header("ETag", format!(r#""{}-etag""#, token).as_str()),
+                )
+                .expect(1)
+                .mount(&server)
+                .await;
+        }
+        let gapi = GithubAPI::with_options(
+            Credentials::Tokens(vec!["first".into(), "second".into()]),
+            Some(server.uri()),
+            ClientOptions::default(),
+        )
+        .unwrap()
+        .with_response_cache(Arc::new(InMemoryLruCache::new(16)));
+        for _ in 0..2 {
+            gapi.get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn test_lru_evicts_least_recently_used() {
+        use super::{CacheKey, CachedResponse, ResponseCache};
+        let cache = InMemoryLruCache::new(2);
+        let key = |url: &str| CacheKey {
+            url: url.into(),
+            token_hash: 1,
+            media_type: None,
+        };
+        let response = CachedResponse {
+            headers: Default::default(),
+            body: "{}".into(),
+        };
+        cache.put(key("a"), response.clone());
+        cache.put(key("b"), response.clone());
+        assert!(cache.get(&key("a")).is_some());
+        cache.put(key("c"), response);
+        assert!(cache.get(&key("a")).is_some());
+        assert!(cache.get(&key("b")).is_none());
+    }
+}
@@ -0,0 +1,267 @@
+use super::{
+    models::{DiffEntry, GitActor, User},
+    with_query, GHAPIError, GithubAPI,
+};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use serde::{Deserialize, Serialize};
+
+/// A raw, non-JSON rendering of a commit or comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DiffFormat {
+    /// A unified diff.
+    Diff,
+    /// One `git format-patch` style mail per commit.
+    Patch,
+}
+
+impl DiffFormat {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            DiffFormat::Diff => "application/vnd.github.diff",
+            DiffFormat::Patch => "application/vnd.github.patch",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ObjectRef {
+    pub sha: String,
+}
+
+/// The git commit object, as opposed to GitHub's view of it.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommitDetails {
+    pub message: String,
+    pub author: Option<GitActor>,
+    pub committer: Option<GitActor>,
+    pub tree: ObjectRef,
+    #[serde(default)]
+    pub comment_count: u32,
+}
+
+/// A commit as the git data API describes it, e.g. one just created.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitCommit {
+    pub sha: String,
+    pub node_id: String,
+    pub html_url: String,
+    pub message: String,
+    pub author: GitActor,
+    pub committer: GitActor,
+    pub tree: ObjectRef,
+    pub parents: Vec<ObjectRef>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommitStats {
+    pub additions: u32,
+    pub deletions: u32,
+    pub total: u32,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Commit {
+    pub sha: String,
+    pub node_id: String,
+    pub commit: CommitDetails,
+    /// The GitHub accounts matching the git author and committer, if any.
+    pub author: Option<User>,
+    pub committer: Option<User>,
+    pub parents: Vec<ObjectRef>,
+    pub html_url: String,
+    /// Only returned when fetching a single commit.
+    pub stats: Option<CommitStats>,
+    pub files: Option<Vec<DiffEntry>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ComparisonStatus {
+    Ahead,
+    Behind,
+    Identical,
+    Diverged,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Comparison {
+    pub status: ComparisonStatus,
+    pub ahead_by: u32,
+    pub behind_by: u32,
+    pub total_commits: u32,
+    pub base_commit: Commit,
+    pub merge_base_commit: Commit,
+    /// At most 250 commits; `total_commits` has the full count.
+    pub commits: Vec<Commit>,
+    #[serde(default)]
+    pub files: Vec<DiffEntry>,
+    pub html_url: String,
+}
+
+/// Filters for listing a repository's commits.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct CommitFilter {
+    /// The branch or SHA to start from. Defaults to the default branch.
+    pub sha: Option<String>,
+    /// Only commits touching this file or directory.
+    pub path: Option<String>,
+    /// A login or email address.
+    pub author: Option<String>,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl GithubAPI {
+    pub async fn list_commits(
+        &self,
+        owner: &str,
+        repo: &str,
+        filter: &CommitFilter,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Commit>, GHAPIError> {
+        let path = with_query(&format!("repos/{}/{}/commits", owner, repo), filter)?;
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    /// Fetches a commit by SHA, branch or tag, including its changed files.
+    pub async fn get_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> Result<Commit, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/commits/{}", owner, repo, reference))
+            .await
+    }
+
+    /// Compares `head` against `base`, both given as a SHA, branch or tag.
+    pub async fn compare(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<Comparison, GHAPIError> {
+        self.get_json(&format!(
+            "repos/{}/{}/compare/{}...{}",
+            owner, repo, base, head
+        ))
+        .await
+    }
+
+    pub async fn get_commit_diff(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        format: DiffFormat,
+    ) -> Result<String, GHAPIError> {
+        let path = format!("repos/{}/{}/commits/{}", owner, repo, reference);
+        self.get_text(&path, format.media_type()).await
+    }
+
+    pub async fn compare_diff(
+        &self,
+        owner: &str,
+        repo: &str,
+        base: &str,
+        head: &str,
+        format: DiffFormat,
+    ) -> Result<String, GHAPIError> {
+        let path = format!("repos/{}/{}/compare/{}...{}", owner, repo, base, head);
+        self.get_text(&path, format.media_type()).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CommitFilter, ComparisonStatus, DiffFormat};
+    use crate::github::GithubAPI;
+    use chrono::{TimeZone, Utc};
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static COMMIT: &str = include_str!("fixtures/commit.json");
+    static COMPARISON: &str = include_str!("fixtures/comparison.json");
+
+    fn json_response(body: String) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_string(body)
+            .insert_header("Content-Type", "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_list_commits_with_filters() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/commits"))
+            .and(query_param("path", "src/sign.rs"))
+            .and(query_param("author", "octocat"))
+            .and(query_param("until", "2022-08-01T00:00:00Z"))
+            .respond_with(json_response(format!("[{}]", COMMIT)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let filter = CommitFilter {
+            path: Some("src/sign.rs".into()),
+            author: Some("octocat".into()),
+            until: Some(Utc.with_ymd_and_hms(2022, 8, 1, 0, 0, 0).unwrap()),
+            ..CommitFilter::default()
+        };
+        let commits = gapi
+            .list_commits("tarkalabs", "ssh-signer", &filter, 30, 10)
+            .await
+            .unwrap();
+        assert_eq!("6dcb09b5b57875f334f61aebed695e2e4193db5e", commits[0].sha);
+        assert_eq!(15, commits[0].stats.as_ref().unwrap().total);
+        assert_eq!(
+            "src/sign.rs",
+            commits[0].files.as_ref().unwrap()[0].filename
+        );
+    }
+
+    #[tokio::test]
+    async fn test_compare_as_json_and_diff() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/compare/main...fix-ed25519",
+            ))
+            .and(header("Accept", "application/vnd.github.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("diff --git a/src/sign.rs"))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/compare/main...fix-ed25519",
+            ))
+            .and(header("Accept", "application/json"))
+            .respond_with(json_response(COMPARISON.to_string()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let comparison = gapi
+            .compare("tarkalabs", "ssh-signer", "main", "fix-ed25519")
+            .await
+            .unwrap();
+        assert_eq!(ComparisonStatus::Ahead, comparison.status);
+        assert_eq!(1, comparison.ahead_by);
+        let diff = gapi
+            .compare_diff(
+                "tarkalabs",
+                "ssh-signer",
+                "main",
+                "fix-ed25519",
+                DiffFormat::Diff,
+            )
+            .await
+            .unwrap();
+        assert!(diff.starts_with("diff --git"));
+    }
+}
@@ -0,0 +1,507 @@
+use super::{
+    commits::GitCommit, models::CommitIdentity, refine_status, stream::ByteStream, with_query,
+    GHAPIError, GithubAPI,
+};
+use error_stack::{IntoReport, Result, ResultExt};
+use percent_encoding::{utf8_percent_encode, AsciiSet, CONTROLS};
+use reqwest::{Method, StatusCode};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+
+/// Characters escaped in a path segment. `/` is among them because paths
+/// are split on it before encoding.
+const PATH_SEGMENT: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'/')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+/// Asks for a file's bytes rather than its JSON description.
+pub(super) static RAW_MEDIA_TYPE: &str = "application/vnd.github.raw";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    File,
+    Dir,
+    Symlink,
+    Submodule,
+}
+
+/// An item in a directory listing.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ContentEntry {
+    #[serde(rename = "type")]
+    pub content_type: ContentType,
+    pub name: String,
+    pub path: String,
+    pub sha: String,
+    pub size: u64,
+    pub download_url: Option<String>,
+}
+
+/// A file with its content decoded.
+#[derive(Debug, Clone, Serialize)]
+pub struct FileContents {
+    pub name: String,
+    pub path: String,
+    /// The blob SHA, needed to update or delete the file.
+    pub sha: String,
+    pub size: u64,
+    #[serde(serialize_with = "encode_base64")]
+    pub content: Vec<u8>,
+}
+
+/// Serializes in the same shape GitHub uses: a file object, an array for a
+/// directory or a bare entry.
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum Contents {
+    File(FileContents),
+    Directory(Vec<ContentEntry>),
+    /// A symlink or submodule, which has no content of its own.
+    Other(ContentEntry),
+}
+
+/// Creates a file, or replaces it when `sha` names its current blob.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileUpdate {
+    pub message: String,
+    /// Sent and accepted base64 encoded, as GitHub expects.
+    #[serde(
+        serialize_with = "encode_base64",
+        deserialize_with = "decode_base64_field"
+    )]
+    pub content: Vec<u8>,
+    /// The blob being replaced. A stale SHA fails with
+    /// [`GHAPIError::Conflict`].
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    /// Defaults to the default branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer: Option<CommitIdentity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<CommitIdentity>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileDeletion {
+    pub message: String,
+    /// The blob being deleted.
+    pub sha: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub branch: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer: Option<CommitIdentity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<CommitIdentity>,
+}
+
+/// The outcome of a file write.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct FileCommit {
+    /// The file as written; `None` after a deletion.
+    pub content: Option<ContentEntry>,
+    pub commit: GitCommit,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ContentsResponse {
+    Directory(Vec<ContentEntry>),
+    Item {
+        #[serde(flatten)]
+        entry: ContentEntry,
+        encoding: Option<String>,
+        content: Option<String>,
+    },
+}
+
+#[derive(Serialize)]
+struct RefQuery<'a> {
+    #[serde(rename = "ref")]
+    reference: Option<&'a str>,
+}
+
+pub(super) fn encode_base64<S: Serializer>(
+    content: &[u8],
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(&base64::encode(content))
+}
+
+pub(super) fn decode_base64_field<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vec<u8>, D::Error> {
+    let content = String::deserialize(deserializer)?;
+    base64::decode(content).map_err(de::Error::custom)
+}
+
+fn decode_base64(content: &str) -> Result<Vec<u8>, GHAPIError> {
+    // GitHub wraps the encoded content every 60 characters.
+    let content: String = content.split_whitespace().collect();
+    base64::decode(content)
+        .report()
+        .change_context(GHAPIError::FailedToDeserialize)
+        .attach_printable("file content is not valid base64")
+}
+
+fn contents_path(
+    owner: &str,
+    repo: &str,
+    path: &str,
+    reference: Option<&str>,
+) -> Result<String, GHAPIError> {
+    let path = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(|segment| utf8_percent_encode(segment, PATH_SEGMENT).to_string())
+        .collect::<Vec<_>>()
+        .join("/");
+    with_query(
+        &format!("repos/{}/{}/contents/{}", owner, repo, path),
+        &RefQuery { reference },
+    )
+}
+
+impl GithubAPI {
+    /// Reads a file or directory at `reference` (a branch, tag or SHA;
+    /// the default branch when `None`). Files over 1 MB, which GitHub
+    /// returns without content, are fetched again as raw bytes.
+    pub async fn get_contents(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        reference: Option<&str>,
+    ) -> Result<Contents, GHAPIError> {
+        let api_path = contents_path(owner, repo, path, reference)?;
+        let (entry, encoding, content) = match self.get_json(&api_path).await? {
+            ContentsResponse::Directory(entries) => return Ok(Contents::Directory(entries)),
+            ContentsResponse::Item {
+                entry,
+                encoding,
+                content,
+            } => (entry, encoding, content),
+        };
+        if entry.content_type != ContentType::File {
+            return Ok(Contents::Other(entry));
+        }
+        let content = match (encoding.as_deref(), content) {
+            (Some("base64"), Some(content)) if !content.is_empty() => decode_base64(&content)?,
+            _ if entry.size == 0 => Vec::new(),
+            _ => self
+                .get_cached_as(self.url(&api_path), Some(RAW_MEDIA_TYPE))
+                .await?
+                .body
+                .to_vec(),
+        };
+        Ok(Contents::File(FileContents {
+            name: entry.name,
+            path: entry.path,
+            sha: entry.sha,
+            size: entry.size,
+            content,
+        }))
+    }
+
+    /// Streams a file's raw bytes without buffering it. For a directory
+    /// GitHub answers with the JSON listing instead.
+    pub async fn stream_contents(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        reference: Option<&str>,
+    ) -> Result<ByteStream, GHAPIError> {
+        let api_path = contents_path(owner, repo, path, reference)?;
+        self.get_stream(self.url(&api_path), RAW_MEDIA_TYPE).await
+    }
+
+    pub async fn put_file(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        update: &FileUpdate,
+    ) -> Result<FileCommit, GHAPIError> {
+        let api_path = contents_path(owner, repo, path, None)?;
+        self.send_json(Method::PUT, &api_path, update)
+            .await
+            .map_err(|report| refine_status(report, StatusCode::CONFLICT, GHAPIError::Conflict))
+    }
+
+    pub async fn delete_file(
+        &self,
+        owner: &str,
+        repo: &str,
+        path: &str,
+        deletion: &FileDeletion,
+    ) -> Result<FileCommit, GHAPIError> {
+        let api_path = contents_path(owner, repo, path, None)?;
+        self.send_json(Method::DELETE, &api_path, deletion)
+            .await
+            .map_err(|report| refine_status(report, StatusCode::CONFLICT, GHAPIError::Conflict))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{contents_path, ContentType, Contents, FileDeletion, FileUpdate};
+    use crate::github::{models::CommitIdentity, GHAPIError, GithubAPI};
+    use wiremock::{
+        matchers::{body_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn json_response(body: serde_json::Value) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(body)
+    }
+
+    #[tokio::test]
+    async fn test_file_contents_are_decoded() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/config/app.toml"))
+            .and(query_param("ref", "v0.2.1"))
+            .respond_with(json_response(serde_json::json!({
+                "type": "file",
+                "encoding": "base64",
+                "size": 22,
+                "name": "app.toml",
+                "path": "config/app.toml",
+                "sha": "3d21ec53a331a6f037a91c368710b99387d012c1",
+                "download_url": null,
+                "content": "W3NlcnZlcl0KcG9ydCA9IDMwMDAK\nICAgIA==\n"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let contents = gapi
+            .get_contents("tarkalabs", "ssh-signer", "config/app.toml", Some("v0.2.1"))
+            .await
+            .unwrap();
+        match contents {
+            Contents::File(file) => {
+                assert_eq!(b"[server]\nport = 3000\n    ".to_vec(), file.content);
+                assert_eq!("3d21ec53a331a6f037a91c368710b99387d012c1", file.sha);
+            }
+            other => panic!("expected a file, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_contents_path_encodes_segments() {
+        assert_eq!(
+            "repos/tarkalabs/ssh-signer/contents/docs/release%20notes%20%231.md?ref=v0.2.1",
+            contents_path(
+                "tarkalabs",
+                "ssh-signer",
+                "/docs/release notes #1.md",
+                Some("v0.2.1")
+            )
+            .unwrap()
+        );
+        assert!(contents_path("tarkalabs", "ssh-signer", "100%?.txt", None)
+            .unwrap()
+            .ends_with("/contents/100%25%3F.txt"));
+    }
+
+    #[tokio::test]
+    async fn test_large_files_use_raw_media_type() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/big.bin"))
+            .and(header("Accept", "application/vnd.github.raw"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(vec![7u8; 2048]))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/big.bin"))
+            .respond_with(json_response(serde_json::json!({
+                "type": "file",
+                "encoding": "none",
+                "size": 2048,
+                "name": "big.bin",
+                "path": "big.bin",
+                "sha": "5b0d5bb3e9e6a3d1a0c8a8b7e7d0c6f6a9b1e0c2",
+                "download_url": null,
+                "content": ""
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        match gapi
+            .get_contents("tarkalabs", "ssh-signer", "big.bin", None)
+            .await
+            .unwrap()
+        {
+            Contents::File(file) => assert_eq!(vec![7u8; 2048], file.content),
+            other => panic!("expected a file, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_directory_listing() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/src"))
+            .respond_with(json_response(serde_json::json!([
+                {
+                    "type": "file",
+                    "size": 1204,
+                    "name": "main.rs",
+                    "path": "src/main.rs",
+                    "sha": "a1b2c3d4e5f60718293a4b5c6d7e8f9012345678",
+                    "download_url": "https://raw.githubusercontent.com/tarkalabs/ssh-signer/main/src/main.rs"
+                },
+                {
+                    "type": "dir",
+                    "size": 0,
+                    "name": "sign",
+                    "path": "src/sign",
+                    "sha": "0f1e2d3c4b5a69788796a5b4c3d2e1f00f1e2d3c",
+                    "download_url": null
+                }
+            ])))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        match gapi
+            .get_contents("tarkalabs", "ssh-signer", "src", None)
+            .await
+            .unwrap()
+        {
+            Contents::Directory(entries) => {
+                assert_eq!(2, entries.len());
+                assert_eq!(ContentType::Dir, entries[1].content_type);
+            }
+            other => panic!("expected a directory, got {:?}", other),
+        }
+    }
+
+    fn file_commit(content: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "content": content,
+            "commit": {
+                "sha": "7638417db6d59f3c431d3e1f261cc637155684cd",
+                "node_id": "C_kwDOEaeHgdoAKDc2Mzg0MTdkYjZkNTlmM2M0MzFkM2UxZjI2",
+                "html_url": "https://github.com/tarkalabs/ssh-signer/commit/7638417db6d59f3c431d3e1f261cc637155684cd",
+                "message": "Bump signer port",
+                "author": {
+                    "name": "config-bot",
+                    "email": "config-bot@tarkalabs.com",
+                    "date": "2022-08-02T10:00:00Z"
+                },
+                "committer": {
+                    "name": "config-bot",
+                    "email": "config-bot@tarkalabs.com",
+                    "date": "2022-08-02T10:00:00Z"
+                },
+                "tree": { "sha": "691272480426f78a0138979dd3ce63b77f706feb" },
+                "parents": [{ "sha": "1acc419d4d6a9ce985db7be48c6349a0475975b5" }]
+            }
+        })
+    }
+
+    #[tokio::test]
+    async fn test_put_file_sends_base64_and_identity() {
+        let server = MockServer::start().await;
+        let bot = CommitIdentity {
+            name: "config-bot".into(),
+            email: "config-bot@tarkalabs.com".into(),
+            date: None,
+        };
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/config/app.toml"))
+            .and(body_json(serde_json::json!({
+                "message": "Bump signer port",
+                "content": "W3NlcnZlcl0KcG9ydCA9IDMwMDEK",
+                "sha": "3d21ec53a331a6f037a91c368710b99387d012c1",
+                "branch": "config-bump",
+                "committer": { "name": "config-bot", "email": "config-bot@tarkalabs.com" }
+            })))
+            .respond_with(ResponseTemplate::new(200).set_body_json(file_commit(
+                serde_json::json!({
+                    "type": "file",
+                    "name": "app.toml",
+                    "path": "config/app.toml",
+                    "sha": "95b966ae1c166bd92f8ae7d1c313e738c731dfc3",
+                    "size": 22,
+                    "download_url": null
+                }),
+            )))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/config/app.toml"))
+            .respond_with(ResponseTemplate::new(409).set_body_json(serde_json::json!({
+                "message": "config/app.toml does not match 3d21ec53a331a6f037a91c368710b99387d012c1"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let update = FileUpdate {
+            message: "Bump signer port".into(),
+            content: b"[server]\nport = 3001\n".to_vec(),
+            sha: Some("3d21ec53a331a6f037a91c368710b99387d012c1".into()),
+            branch: Some("config-bump".into()),
+            committer: Some(bot),
+            author: None,
+        };
+        let written = gapi
+            .put_file("tarkalabs", "ssh-signer", "config/app.toml", &update)
+            .await
+            .unwrap();
+        assert_eq!(
+            "95b966ae1c166bd92f8ae7d1c313e738c731dfc3",
+            written.content.unwrap().sha
+        );
+        assert_eq!("config-bot", written.commit.author.name);
+        let err = gapi
+            .put_file("tarkalabs", "ssh-signer", "config/app.toml", &update)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), GHAPIError::Conflict(_)));
+    }
+
+    #[tokio::test]
+    async fn test_delete_file() {
+        let server = MockServer::start().await;
+        Mock::given(method("DELETE"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/config/old.toml"))
+            .and(body_json(serde_json::json!({
+                "message": "Remove old config",
+                "sha": "3d21ec53a331a6f037a91c368710b99387d012c1"
+            })))
+            .respond_with(
+                ResponseTemplate::new(200).set_body_json(file_commit(serde_json::Value::Null)),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let deletion = FileDeletion {
+            message: "Remove old config".into(),
+            sha: "3d21ec53a331a6f037a91c368710b99387d012c1".into(),
+            branch: None,
+            committer: None,
+            author: None,
+        };
+        let deleted = gapi
+            .delete_file("tarkalabs", "ssh-signer", "config/old.toml", &deletion)
+            .await
+            .unwrap();
+        assert!(deleted.content.is_none());
+        assert_eq!(1, deleted.commit.parents.len());
+    }
+}
@@ -0,0 +1,491 @@
+use super::{
+    commits::{GitCommit, ObjectRef},
+    contents::{decode_base64_field, encode_base64},
+    models::CommitIdentity,
+    GHAPIError, GithubAPI,
+};
+use error_stack::{Report, Result};
+use futures::{stream, StreamExt, TryStreamExt};
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+/// How many blobs a multi-file commit creates at once. GitHub's secondary
+/// rate limits punish bursts of concurrent content-creating requests.
+const BLOB_CONCURRENCY: usize = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FileMode {
+    #[serde(rename = "100644")]
+    File,
+    #[serde(rename = "100755")]
+    Executable,
+    #[serde(rename = "040000")]
+    Directory,
+    #[serde(rename = "160000")]
+    Submodule,
+    #[serde(rename = "120000")]
+    Symlink,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ObjectType {
+    Blob,
+    Tree,
+    Commit,
+    Tag,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TreeEntry {
+    pub path: String,
+    pub mode: FileMode,
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+    pub sha: String,
+    /// Only set for blobs.
+    pub size: Option<u64>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Tree {
+    pub sha: String,
+    pub tree: Vec<TreeEntry>,
+    #[serde(default)]
+    pub truncated: bool,
+}
+
+/// An entry of a tree being built. A `sha` of `None` deletes `path` from
+/// the base tree.
+#[derive(Debug, Clone, Serialize)]
+pub struct NewTreeEntry {
+    pub path: String,
+    pub mode: FileMode,
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+    pub sha: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewTree {
+    /// The tree the entries are applied to. Without one, the new tree holds
+    /// only the given entries.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base_tree: Option<String>,
+    pub tree: Vec<NewTreeEntry>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct NewCommit {
+    pub message: String,
+    pub tree: String,
+    pub parents: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<CommitIdentity>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub committer: Option<CommitIdentity>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitObject {
+    pub sha: String,
+    #[serde(rename = "type")]
+    pub object_type: ObjectType,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Reference {
+    /// The full name, e.g. `refs/heads/main`.
+    #[serde(rename = "ref")]
+    pub name: String,
+    pub node_id: String,
+    pub object: GitObject,
+}
+
+#[derive(Serialize)]
+struct NewBlob<'a> {
+    #[serde(serialize_with = "encode_base64")]
+    content: &'a [u8],
+    encoding: &'static str,
+}
+
+#[derive(Serialize)]
+struct NewRef<'a> {
+    #[serde(rename = "ref")]
+    name: &'a str,
+    sha: &'a str,
+}
+
+#[derive(Serialize)]
+struct RefUpdate<'a> {
+    sha: &'a str,
+    force: bool,
+}
+
+/// One file to change in a [`FileChanges`] commit.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "action", rename_all = "lowercase")]
+pub enum FileChange {
+    Write {
+        path: String,
+        /// Base64 encoded when deserialized.
+        #[serde(deserialize_with = "decode_base64_field")]
+        content: Vec<u8>,
+        #[serde(default)]
+        executable: bool,
+    },
+    Delete {
+        path: String,
+    },
+}
+
+/// Several file changes to make as a single commit on `branch`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileChanges {
+    pub branch: String,
+    pub message: String,
+    pub changes: Vec<FileChange>,
+    pub author: Option<CommitIdentity>,
+    pub committer: Option<CommitIdentity>,
+}
+
+/// Turns GitHub's refusal of a non-fast-forward ref update (422) into a
+/// conflict: the branch moved since the new commit was based on it.
+fn refine_fast_forward(report: Report<GHAPIError>) -> Report<GHAPIError> {
+    match report.current_context() {
+        GHAPIError::ResponseUnsuccessful(err)
+            if err.status == StatusCode::UNPROCESSABLE_ENTITY
+                && err.message.contains("fast forward") =>
+        {
+            let refined = GHAPIError::Conflict(err.clone());
+            report.change_context(refined)
+        }
+        _ => report,
+    }
+}
+
+impl GithubAPI {
+    pub async fn create_blob(
+        &self,
+        owner: &str,
+        repo: &str,
+        content: &[u8],
+    ) -> Result<ObjectRef, GHAPIError> {
+        let path = format!("repos/{}/{}/git/blobs", owner, repo);
+        let blob = NewBlob {
+            content,
+            encoding: "base64",
+        };
+        self.send_json(Method::POST, &path, &blob).await
+    }
+
+    pub async fn create_tree(
+        &self,
+        owner: &str,
+        repo: &str,
+        tree: &NewTree,
+    ) -> Result<Tree, GHAPIError> {
+        let path = format!("repos/{}/{}/git/trees", owner, repo);
+        self.send_json(Method::POST, &path, tree).await
+    }
+
+    pub async fn get_git_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> Result<GitCommit, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/git/commits/{}", owner, repo, sha))
+            .await
+    }
+
+    pub async fn create_git_commit(
+        &self,
+        owner: &str,
+        repo: &str,
+        commit: &NewCommit,
+    ) -> Result<GitCommit, GHAPIError> {
+        let path = format!("repos/{}/{}/git/commits", owner, repo);
+        self.send_json(Method::POST, &path, commit).await
+    }
+
+    /// Fetches a ref given without its `refs/` prefix, e.g. `heads/main`.
+    pub async fn get_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+    ) -> Result<Reference, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/git/ref/{}", owner, repo, reference))
+            .await
+    }
+
+    /// Creates `name`, which must be fully qualified such as
+    /// `refs/heads/main`, pointing at `sha`.
+    pub async fn create_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+        sha: &str,
+    ) -> Result<Reference, GHAPIError> {
+        let path = format!("repos/{}/{}/git/refs", owner, repo);
+        self.send_json(Method::POST, &path, &NewRef { name, sha })
+            .await
+    }
+
+    /// Points `reference` (e.g. `heads/main`) at `sha`. Unless `force` is
+    /// set, GitHub only allows fast-forwards and anything else fails with
+    /// [`GHAPIError::Conflict`].
+    pub async fn update_ref(
+        &self,
+        owner: &str,
+        repo: &str,
+        reference: &str,
+        sha: &str,
+        force: bool,
+    ) -> Result<Reference, GHAPIError> {
+        let path = format!("repos/{}/{}/git/refs/{}", owner, repo, reference);
+        self.send_json(Method::PATCH, &path, &RefUpdate { sha, force })
+            .await
+            .map_err(refine_fast_forward)
+    }
+
+    /// The tree entry for `change`, uploading the new content as a blob.
+    async fn tree_entry(
+        &self,
+        owner: &str,
+        repo: &str,
+        change: &FileChange,
+    ) -> Result<NewTreeEntry, GHAPIError> {
+        Ok(match change {
+            FileChange::Write {
+                path,
+                content,
+                executable,
+            } => NewTreeEntry {
+                path: path.clone(),
+                mode: if *executable {
+                    FileMode::Executable
+                } else {
+                    FileMode::File
+                },
+                object_type: ObjectType::Blob,
+                sha: Some(self.create_blob(owner, repo, content).await?.sha),
+            },
+            FileChange::Delete { path } => NewTreeEntry {
+                path: path.clone(),
+                mode: FileMode::File,
+                object_type: ObjectType::Blob,
+                sha: None,
+            },
+        })
+    }
+
+    /// Commits all of `changes` on top of the branch head as one commit and
+    /// fast-forwards the branch to it. If the branch moves in the meantime
+    /// this fails with [`GHAPIError::Conflict`] and nothing is changed.
+    pub async fn commit_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        changes: &FileChanges,
+    ) -> Result<GitCommit, GHAPIError> {
+        let branch = format!("heads/{}", changes.branch);
+        let head = self.get_ref(owner, repo, &branch).await?.object.sha;
+        let base_tree = self.get_git_commit(owner, repo, &head).await?.tree.sha;
+        let entries: Vec<_> = changes
+            .changes
+            .iter()
+            .map(|change| self.tree_entry(owner, repo, change))
+            .collect();
+        let entries = stream::iter(entries)
+            .buffered(BLOB_CONCURRENCY)
+            .try_collect()
+            .await?;
+        let tree = NewTree {
+            base_tree: Some(base_tree),
+            tree: entries,
+        };
+        let tree = self.create_tree(owner, repo, &tree).await?;
+        let commit = NewCommit {
+            message: changes.message.clone(),
+            tree: tree.sha,
+            parents: vec![head],
+            author: changes.author.clone(),
+            committer: changes.committer.clone(),
+        };
+        let commit = self.create_git_commit(owner, repo, &commit).await?;
+        self.update_ref(owner, repo, &branch, &commit.sha, false)
+            .await?;
+        Ok(commit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileChange, FileChanges};
+    use crate::github::{GHAPIError, GithubAPI};
+    use wiremock::{
+        matchers::{body_json, body_partial_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    static HEAD: &str = "1acc419d4d6a9ce985db7be48c6349a0475975b5";
+    static BASE_TREE: &str = "691272480426f78a0138979dd3ce63b77f706feb";
+    static NEW_TREE: &str = "cd8274d15fa3ae2ab983129fb037999f264ba9a7";
+    static NEW_COMMIT: &str = "7638417db6d59f3c431d3e1f261cc637155684cd";
+
+    fn git_commit(sha: &str, tree: &str, parents: &[&str]) -> serde_json::Value {
+        let actor = serde_json::json!({
+            "name": "codegen-bot",
+            "email": "codegen-bot@tarkalabs.com",
+            "date": "2022-08-02T10:00:00Z"
+        });
+        serde_json::json!({
+            "sha": sha,
+            "node_id": "C_kwDOEaeHgdoAKDc2Mzg0MTdkYjZkNTlmM2M0MzFkM2UxZjI2",
+            "html_url": format!("https://github.com/tarkalabs/ssh-signer/commit/{}", sha),
+            "message": "Regenerate clients",
+            "author": actor,
+            "committer": actor,
+            "tree": { "sha": tree },
+            "parents": parents.iter().map(|sha| serde_json::json!({ "sha": sha })).collect::<Vec<_>>()
+        })
+    }
+
+    /// Mocks every step of `commit_files` up to the ref update.
+    async fn mount_commit_steps(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/ref/heads/codegen"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ref": "refs/heads/codegen",
+                "node_id": "MDM6UmVmcmVmcy9oZWFkcy9jb2RlZ2Vu",
+                "object": { "sha": HEAD, "type": "commit" }
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/tarkalabs/ssh-signer/git/commits/{}",
+                HEAD
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(git_commit(
+                HEAD,
+                BASE_TREE,
+                &[],
+            )))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/blobs"))
+            .and(body_json(serde_json::json!({
+                "content": "cHViIGZuIHNpZ24oKSB7fQo=",
+                "encoding": "base64"
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "sha": "3a0f86fb8db8eea7ccbb9a95f325ddbedfb25e15"
+            })))
+            .expect(1)
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/trees"))
+            .and(body_json(serde_json::json!({
+                "base_tree": BASE_TREE,
+                "tree": [
+                    {
+                        "path": "src/client.rs",
+                        "mode": "100644",
+                        "type": "blob",
+                        "sha": "3a0f86fb8db8eea7ccbb9a95f325ddbedfb25e15"
+                    },
+                    { "path": "src/legacy.rs", "mode": "100644", "type": "blob", "sha": null }
+                ]
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "sha": NEW_TREE,
+                "tree": [],
+                "truncated": false
+            })))
+            .mount(server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/commits"))
+            .and(body_partial_json(serde_json::json!({
+                "tree": NEW_TREE,
+                "parents": [HEAD]
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(git_commit(
+                NEW_COMMIT,
+                NEW_TREE,
+                &[HEAD],
+            )))
+            .mount(server)
+            .await;
+    }
+
+    fn changes() -> FileChanges {
+        FileChanges {
+            branch: "codegen".into(),
+            message: "Regenerate clients".into(),
+            changes: vec![
+                FileChange::Write {
+                    path: "src/client.rs".into(),
+                    content: b"pub fn sign() {}\n".to_vec(),
+                    executable: false,
+                },
+                FileChange::Delete {
+                    path: "src/legacy.rs".into(),
+                },
+            ],
+            author: None,
+            committer: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_commit_files_fast_forwards_branch() {
+        let server = MockServer::start().await;
+        mount_commit_steps(&server).await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/refs/heads/codegen"))
+            .and(body_json(
+                serde_json::json!({ "sha": NEW_COMMIT, "force": false }),
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ref": "refs/heads/codegen",
+                "node_id": "MDM6UmVmcmVmcy9oZWFkcy9jb2RlZ2Vu",
+                "object": { "sha": NEW_COMMIT, "type": "commit" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let commit = gapi
+            .commit_files("tarkalabs", "ssh-signer", &changes())
+            .await
+            .unwrap();
+        assert_eq!(NEW_COMMIT, commit.sha);
+        assert_eq!(HEAD, commit.parents[0].sha);
+    }
+
+    #[tokio::test]
+    async fn test_moved_branch_is_a_conflict() {
+        let server = MockServer::start().await;
+        mount_commit_steps(&server).await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/refs/heads/codegen"))
+            .respond_with(ResponseTemplate::new(422).set_body_json(serde_json::json!({
+                "message": "Update is not a fast forward"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let err = gapi
+            .commit_files("tarkalabs", "ssh-signer", &changes())
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), GHAPIError::Conflict(_)));
+    }
+}
@@ -0,0 +1,284 @@
+use super::{models::User, with_query, GHAPIError, GithubAPI};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use reqwest::Method;
+use serde::{Deserialize, Deserializer, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IssueState {
+    Open,
+    Closed,
+}
+
+/// Which issues to list by state. GitHub defaults to `Open`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StateFilter {
+    Open,
+    Closed,
+    All,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Label {
+    pub id: u64,
+    pub name: String,
+    pub color: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub default: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Milestone {
+    pub id: u64,
+    pub number: u64,
+    pub title: String,
+    pub description: Option<String>,
+    pub state: IssueState,
+    pub due_on: Option<DateTime<Utc>>,
+}
+
+/// Set on issues that are really pull requests, which GitHub includes in
+/// issue listings.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IssuePullRequest {
+    pub html_url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Issue {
+    pub id: u64,
+    pub node_id: String,
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    pub state: IssueState,
+    /// `completed`, `not_planned` or `reopened`.
+    pub state_reason: Option<String>,
+    pub user: User,
+    pub labels: Vec<Label>,
+    pub assignees: Vec<User>,
+    pub milestone: Option<Milestone>,
+    pub comments: u32,
+    pub locked: bool,
+    pub html_url: String,
+    pub pull_request: Option<IssuePullRequest>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IssueComment {
+    pub id: u64,
+    pub body: String,
+    pub user: User,
+    pub html_url: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Filters for listing a repository's issues.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IssueFilter {
+    pub state: Option<StateFilter>,
+    /// Comma separated label names; issues must have all of them.
+    pub labels: Option<String>,
+    /// A login, `none` for unassigned issues or `*` for any assignee.
+    pub assignee: Option<String>,
+    /// Only issues updated at or after this time.
+    pub since: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewIssue {
+    pub title: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub labels: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub assignees: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub milestone: Option<u64>,
+}
+
+/// Changes to an issue. Fields left unset are not sent, so they keep
+/// their current value.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct IssueUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<IssueState>,
+    /// Replaces every label on the issue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub labels: Option<Vec<String>>,
+    /// Replaces every assignee of the issue.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub assignees: Option<Vec<String>>,
+    /// `Some(None)`, sent as `null`, removes the milestone.
+    #[serde(
+        default,
+        deserialize_with = "nullable",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub milestone: Option<Option<u64>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewComment {
+    pub body: String,
+}
+
+/// Tells an explicit `null` (`Some(None)`) apart from a missing field.
+fn nullable<'de, D, T>(deserializer: D) -> std::result::Result<Option<Option<T>>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
+impl GithubAPI {
+    pub async fn list_issues(
+        &self,
+        owner: &str,
+        repo: &str,
+        filter: &IssueFilter,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Issue>, GHAPIError> {
+        let path = with_query(&format!("repos/{}/{}/issues", owner, repo), filter)?;
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn get_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+    ) -> Result<Issue, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/issues/{}", owner, repo, number))
+            .await
+    }
+
+    pub async fn create_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        issue: &NewIssue,
+    ) -> Result<Issue, GHAPIError> {
+        let path = format!("repos/{}/{}/issues", owner, repo);
+        self.send_json(Method::POST, &path, issue).await
+    }
+
+    pub async fn update_issue(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        update: &IssueUpdate,
+    ) -> Result<Issue, GHAPIError> {
+        let path = format!("repos/{}/{}/issues/{}", owner, repo, number);
+        self.send_json(Method::PATCH, &path, update).await
+    }
+
+    pub async fn list_issue_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<IssueComment>, GHAPIError> {
+        let path = format!("repos/{}/{}/issues/{}/comments", owner, repo, number);
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn create_issue_comment(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        comment: &NewComment,
+    ) -> Result<IssueComment, GHAPIError> {
+        let path = format!("repos/{}/{}/issues/{}/comments", owner, repo, number);
+        self.send_json(Method::POST, &path, comment).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IssueFilter, IssueState, IssueUpdate, StateFilter};
+    use crate::github::GithubAPI;
+    use chrono::{TimeZone, Utc};
+    use wiremock::{
+        matchers::{body_json, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static ISSUE: &str = include_str!("fixtures/issue.json");
+
+    fn json_response(body: String) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_string(body)
+            .insert_header("Content-Type", "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_list_issues_with_filters() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/issues"))
+            .and(query_param("state", "all"))
+            .and(query_param("labels", "bug,triage"))
+            .and(query_param("since", "2022-07-01T00:00:00Z"))
+            .respond_with(json_response(format!("[{}]", ISSUE)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let filter = IssueFilter {
+            state: Some(StateFilter::All),
+            labels: Some("bug,triage".into()),
+            since: Some(Utc.with_ymd_and_hms(2022, 7, 1, 0, 0, 0).unwrap()),
+            ..IssueFilter::default()
+        };
+        let issues = gapi
+            .list_issues("tarkalabs", "ssh-signer", &filter, 30, 10)
+            .await
+            .unwrap();
+        assert_eq!(1, issues.len());
+        assert_eq!(17, issues[0].number);
+        assert_eq!("bug", issues[0].labels[0].name);
+        assert_eq!("octocat", issues[0].assignees[0].login);
+    }
+
+    #[tokio::test]
+    async fn test_update_issue_sends_only_changes() {
+        let server = MockServer::start().await;
+        Mock::given(method("PATCH"))
+            .and(path("/repos/tarkalabs/ssh-signer/issues/17"))
+            .and(body_json(serde_json::json!({
+                "state": "closed",
+                "milestone": null
+            })))
+            .respond_with(json_response(ISSUE.replace(r#""open""#, r#""closed""#)))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let update: IssueUpdate =
+            serde_json::from_str(r#"{ "state": "closed", "milestone": null }"#).unwrap();
+        assert_eq!(Some(None), update.milestone);
+        let issue = gapi
+            .update_issue("tarkalabs", "ssh-signer", 17, &update)
+            .await
+            .unwrap();
+        assert_eq!(IssueState::Closed, issue.state);
+    }
+}
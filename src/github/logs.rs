@@ -0,0 +1,327 @@
+use super::{GHAPIError, GithubAPI};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use flate2::read::DeflateDecoder;
+use futures::StreamExt;
+use std::io::Read;
+
+/// One log file of a run's log archive, named after its job and step.
+#[derive(Debug, Clone)]
+pub struct LogFile {
+    pub name: String,
+    pub content: String,
+}
+
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const CENTRAL_DIRECTORY_ENTRY: u32 = 0x0201_4b50;
+const LOCAL_FILE_HEADER: u32 = 0x0403_4b50;
+const STORED: u16 = 0;
+const DEFLATED: u16 = 8;
+/// Zip64 archives mark the fields they moved to the zip64 records this way.
+const ZIP64_U16: u16 = u16::MAX;
+const ZIP64_U32: u32 = u32::MAX;
+/// The largest log download, archive or plain text, that is read.
+const MAX_DOWNLOAD_BYTES: usize = 64 << 20;
+/// The most log text one archive may unpack to, across all its files.
+const MAX_LOG_BYTES: u64 = 256 << 20;
+
+fn malformed(reason: &str) -> Report<GHAPIError> {
+    Report::new(GHAPIError::FailedToDeserialize)
+        .attach_printable(format!("malformed log archive: {}", reason))
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> Result<u16, GHAPIError> {
+    bytes
+        .get(at..at + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| malformed("truncated"))
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> Result<u32, GHAPIError> {
+    bytes
+        .get(at..at + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| malformed("truncated"))
+}
+
+/// Reads every file of a zip archive through its central directory. Only
+/// the stored and deflate methods GitHub uses are supported, and no zip64.
+/// Fails once the files unpack to more than `max_bytes` in total.
+fn unzip(archive: &[u8], max_bytes: u64) -> Result<Vec<LogFile>, GHAPIError> {
+    // The end record sits last, followed only by a comment of up to 64 KiB.
+    let eocd = (0..archive.len().saturating_sub(21))
+        .rev()
+        .take(22 + usize::from(u16::MAX))
+        .find(|&at| u32_at(archive, at).ok() == Some(END_OF_CENTRAL_DIRECTORY))
+        .ok_or_else(|| malformed("no end of central directory"))?;
+    let entries = u16_at(archive, eocd + 10)?;
+    let central = u32_at(archive, eocd + 16)?;
+    if entries == ZIP64_U16 || central == ZIP64_U32 {
+        return Err(malformed("zip64 is not supported"));
+    }
+    let mut at = central as usize;
+    let mut remaining = max_bytes;
+    let mut files = Vec::with_capacity(entries.into());
+    for _ in 0..entries {
+        if u32_at(archive, at)? != CENTRAL_DIRECTORY_ENTRY {
+            return Err(malformed("bad central directory entry"));
+        }
+        let method = u16_at(archive, at + 10)?;
+        let compressed_size = u32_at(archive, at + 20)?;
+        let size = u32_at(archive, at + 24)?;
+        let name_len = u16_at(archive, at + 28)? as usize;
+        let extra_len = u16_at(archive, at + 30)? as usize;
+        let comment_len = u16_at(archive, at + 32)? as usize;
+        let local = u32_at(archive, at + 42)?;
+        if [compressed_size, size, local].contains(&ZIP64_U32) {
+            return Err(malformed("zip64 is not supported"));
+        }
+        if u64::from(size) > remaining {
+            return Err(malformed("too large"));
+        }
+        let (compressed_size, local) = (compressed_size as usize, local as usize);
+        let name = archive
+            .get(at + 46..at + 46 + name_len)
+            .ok_or_else(|| malformed("truncated"))?;
+        let name = String::from_utf8_lossy(name).into_owned();
+        at += 46 + name_len + extra_len + comment_len;
+        if name.ends_with('/') {
+            continue;
+        }
+
+        if u32_at(archive, local)? != LOCAL_FILE_HEADER {
+            return Err(malformed("bad local file header"));
+        }
+        let start = local
+            + 30
+            + u16_at(archive, local + 26)? as usize
+            + u16_at(archive, local + 28)? as usize;
+        let data = archive
+            .get(start..start + compressed_size)
+            .ok_or_else(|| malformed("truncated"))?;
+        let content = match method {
+            STORED => data.to_vec(),
+            DEFLATED => {
+                // The declared size is not trusted; stop inflating just past
+                // what is left of the limit.
+                let mut content = Vec::new();
+                DeflateDecoder::new(data)
+                    .take(remaining + 1)
+                    .read_to_end(&mut content)
+                    .report()
+                    .change_context(GHAPIError::FailedToDeserialize)?;
+                content
+            }
+            _ => return Err(malformed("unsupported compression method")),
+        };
+        remaining = remaining
+            .checked_sub(content.len() as u64)
+            .ok_or_else(|| malformed("too large"))?;
+        files.push(LogFile {
+            name,
+            content: String::from_utf8_lossy(&content).into_owned(),
+        });
+    }
+    Ok(files)
+}
+
+/// Reads a response body, failing once it exceeds `limit` bytes.
+async fn read_capped(resp: reqwest::Response, limit: usize) -> Result<Vec<u8>, GHAPIError> {
+    let too_large = || {
+        Report::new(GHAPIError::FailedToDeserialize)
+            .attach_printable(format!("log download is larger than {} bytes", limit))
+    };
+    if resp.content_length().unwrap_or_default() > limit as u64 {
+        return Err(too_large());
+    }
+    // The length may be missing, so the limit is enforced while reading.
+    let mut bytes = Vec::new();
+    let mut body = resp.bytes_stream();
+    while let Some(chunk) = body.next().await {
+        let chunk = chunk.report().change_context(GHAPIError::RequestFailed)?;
+        if bytes.len() + chunk.len() > limit {
+            return Err(too_large());
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
+}
+
+impl GithubAPI {
+    /// Downloads the log archive of a run's latest attempt from wherever
+    /// GitHub redirects to, and unpacks it into one file per job and step,
+    /// sorted by name.
+    pub async fn get_run_logs(
+        &self,
+        owner: &str,
+        repo: &str,
+        run_id: u64,
+    ) -> Result<Vec<LogFile>, GHAPIError> {
+        let path = format!("repos/{}/{}/actions/runs/{}/logs", owner, repo, run_id);
+        let resp = self.send(self.client.get(self.url(&path))).await?;
+        let archive = read_capped(resp, MAX_DOWNLOAD_BYTES).await?;
+        let mut files = unzip(&archive, MAX_LOG_BYTES)?;
+        files.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(files)
+    }
+
+    /// The complete log of one job as plain text.
+    pub async fn get_job_logs(
+        &self,
+        owner: &str,
+        repo: &str,
+        job_id: u64,
+    ) -> Result<String, GHAPIError> {
+        let path = format!("repos/{}/{}/actions/jobs/{}/logs", owner, repo, job_id);
+        let resp = self.send(self.client.get(self.url(&path))).await?;
+        let log = read_capped(resp, MAX_DOWNLOAD_BYTES).await?;
+        Ok(String::from_utf8_lossy(&log).into_owned())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_capped, unzip, MAX_LOG_BYTES};
+    use crate::github::GithubAPI;
+    use flate2::{write::DeflateEncoder, Compression};
+    use std::io::Write;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    /// Builds a zip archive, deflating entries whose name ends in `.txt`
+    /// and storing the rest.
+    fn zip_of(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut archive = Vec::new();
+        let mut central = Vec::new();
+        for (name, content) in entries {
+            let (method, data) = if name.ends_with(".txt") {
+                let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+                encoder.write_all(content.as_bytes()).unwrap();
+                (8u16, encoder.finish().unwrap())
+            } else {
+                (0u16, content.as_bytes().to_vec())
+            };
+            let offset = archive.len() as u32;
+            let header = |sig: u32, central: bool| {
+                let mut h = sig.to_le_bytes().to_vec();
+                if central {
+                    h.extend(20u16.to_le_bytes());
+                }
+                h.extend(20u16.to_le_bytes());
+                h.extend(0u16.to_le_bytes());
+                h.extend(method.to_le_bytes());
+                h.extend([0u8; 8]);
+                h.extend((data.len() as u32).to_le_bytes());
+                h.extend((content.len() as u32).to_le_bytes());
+                h.extend((name.len() as u16).to_le_bytes());
+                h.extend(0u16.to_le_bytes());
+                if central {
+                    h.extend([0u8; 10]);
+                    h.extend(offset.to_le_bytes());
+                }
+                h.extend(name.as_bytes());
+                h
+            };
+            let local = header(0x0403_4b50, false);
+            central.extend(header(0x0201_4b50, true));
+            archive.extend(local);
+            archive.extend(&data);
+        }
+        let central_offset = archive.len() as u32;
+        archive.extend(&central);
+        archive.extend(0x0605_4b50u32.to_le_bytes());
+        archive.extend([0u8; 4]);
+        archive.extend((entries.len() as u16).to_le_bytes());
+        archive.extend((entries.len() as u16).to_le_bytes());
+        archive.extend((central.len() as u32).to_le_bytes());
+        archive.extend(central_offset.to_le_bytes());
+        archive.extend(0u16.to_le_bytes());
+        archive
+    }
+
+    #[test]
+    fn test_unzip() {
+        let archive = zip_of(&[
+            ("test/", ""),
+            ("test/1_Set up job.txt", "Runner version 2.294.0\n"),
+            ("raw.log", "stored, not deflated"),
+        ]);
+        let files = unzip(&archive, MAX_LOG_BYTES).unwrap();
+        assert_eq!(2, files.len());
+        assert_eq!("test/1_Set up job.txt", files[0].name);
+        assert_eq!("Runner version 2.294.0\n", files[0].content);
+        assert_eq!("stored, not deflated", files[1].content);
+        assert!(unzip(b"not a zip archive at all", MAX_LOG_BYTES).is_err());
+    }
+
+    #[test]
+    fn test_unzip_limits() {
+        let bomb = "0".repeat(1 << 20);
+        let archive = zip_of(&[("test/1_Bomb.txt", &bomb)]);
+        assert!(archive.len() < 4096);
+        assert!(unzip(&archive, 1 << 20).is_ok());
+        assert!(unzip(&archive, (1 << 20) - 1).is_err());
+        // Understating the size does not get past the limit either.
+        let mut lying = archive.clone();
+        let central = lying.len() - 6;
+        let central = u32::from_le_bytes(lying[central..central + 4].try_into().unwrap());
+        let at = central as usize + 24;
+        lying[at..at + 4].copy_from_slice(&1u32.to_le_bytes());
+        assert!(unzip(&lying, (1 << 20) - 1).is_err());
+
+        // A zip64 archive points at its zip64 end record instead.
+        let mut archive = zip_of(&[("raw.log", "stored")]);
+        let at = archive.len() - 6;
+        archive[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(unzip(&archive, MAX_LOG_BYTES).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_logs_follow_redirect() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/runs/2810457233/logs",
+            ))
+            .respond_with(ResponseTemplate::new(302).insert_header(
+                "Location",
+                format!("{}/storage/logs.zip", server.uri()).as_str(),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/logs.zip"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(zip_of(&[
+                ("test/2_Run cargo test.txt", "test sign ... FAILED\n"),
+                ("test/1_Set up job.txt", "Runner version 2.294.0\n"),
+            ])))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let files = gapi
+            .get_run_logs("tarkalabs", "ssh-signer", 2810457233)
+            .await
+            .unwrap();
+        assert_eq!("test/1_Set up job.txt", files[0].name);
+        assert_eq!("test sign ... FAILED\n", files[1].content);
+    }
+
+    #[tokio::test]
+    async fn test_downloads_are_capped() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/actions/jobs/7/logs"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("12345"))
+            .mount(&server)
+            .await;
+        let url = format!(
+            "{}/repos/tarkalabs/ssh-signer/actions/jobs/7/logs",
+            server.uri()
+        );
+        let resp = reqwest::get(&url).await.unwrap();
+        assert_eq!(b"12345".to_vec(), read_capped(resp, 5).await.unwrap());
+        let resp = reqwest::get(&url).await.unwrap();
+        assert!(read_capped(resp, 4).await.is_err());
+    }
+}
@@ -1,19 +1,123 @@
-use error_stack::{IntoReport, Result, ResultExt};
+use auth::{Auth, Credential};
+use error_stack::{IntoReport, Report, Result, ResultExt};
 use reqwest::{
-    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, USER_AGENT},
-    Client,
+    header::{HeaderMap, HeaderValue, ACCEPT, AUTHORIZATION, CONTENT_RANGE, USER_AGENT},
+    Certificate, Client, Identity, Method, Request, RequestBuilder, Response, StatusCode,
 };
-use serde::Deserialize;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::error::Error;
 use std::fmt::{write, Display};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod actions;
+mod artifacts;
+mod auth;
+mod branches;
+mod cache;
+mod commits;
+mod contents;
+mod git;
+mod issues;
+mod logs;
+mod models;
+mod pagination;
+mod pulls;
+mod rate_limit;
+mod releases;
+mod retry;
+mod secrets;
+mod stream;
+mod tags;
+mod token_pool;
+
+pub use actions::{RunFilter, WorkflowDispatch};
+pub use auth::{AppCredentials, Credentials};
+pub use branches::{BranchProtection, ProtectionPolicy, ProtectionUpdate};
+pub use cache::{InMemoryLruCache, ResponseCache};
+pub use commits::{CommitFilter, DiffFormat, GitCommit};
+pub use contents::{FileCommit, FileDeletion, FileUpdate};
+pub use git::{FileChanges, Reference};
+pub use issues::{Issue, IssueComment, IssueFilter, IssueUpdate, NewComment, NewIssue};
+pub use models::Repository;
+pub use pagination::DEFAULT_PER_PAGE;
+pub use pulls::{
+    MergeRequest, MergeResult, NewReview, NewReviewComment, PullRequest, PullRequestFilter,
+    ReviewerRequest,
+};
+pub use rate_limit::RateLimit;
+pub use releases::{AssetUpload, NewRelease, Release, ReleaseAsset, ReleaseUpdate};
+pub use retry::RetryPolicy;
+pub use secrets::{NewVariable, SecretOutcome, SecretScope, SecretValue, SecretWrite};
+pub use stream::ByteStream;
+pub use tags::{AnnotatedTag, NewTag};
+pub use token_pool::TokenHealth;
+
 static BASE_URL: &str = "https://api.github.com";
+static UPLOADS_URL: &str = "https://uploads.github.com";
+/// GitHub Enterprise Server serves the REST API and uploads under these
+/// prefixes of the instance URL.
+static ENTERPRISE_API_PREFIX: &str = "/api/v3";
+static ENTERPRISE_UPLOADS_PREFIX: &str = "/api/uploads";
 
 #[derive(Debug)]
 pub enum GHAPIError {
     ClientCreationFailed,
     RequestFailed,
-    ResponseUnsuccessful(String),
+    ResponseUnsuccessful(UpstreamError),
     FailedToDeserialize,
+    /// Obtaining credentials for the request failed, e.g. a GitHub App
+    /// installation token could not be issued.
+    AuthenticationFailed,
+    /// GitHub refused the operation in the resource's current state, e.g. a
+    /// pull request that cannot be merged (405).
+    NotMergeable(UpstreamError),
+    /// The resource changed underneath the request (409), e.g. the head of
+    /// a pull request no longer matches the expected SHA.
+    Conflict(UpstreamError),
+    /// The token's rate limit is exhausted until `reset_at` (Unix seconds).
+    RateLimited {
+        reset_at: u64,
+    },
+}
+
+/// The error GitHub returned for an unsuccessful response, along with its
+/// HTTP status.
+#[derive(Debug, Clone)]
+pub struct UpstreamError {
+    pub status: StatusCode,
+    pub message: String,
+    pub documentation_url: Option<String>,
+    /// The `Content-Range` sent with a 416, naming the resource's size.
+    pub content_range: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct UpstreamErrorBody {
+    message: String,
+    documentation_url: Option<String>,
+}
+
+impl UpstreamError {
+    /// Builds an error from the status and raw body of a response. Bodies
+    /// that are not GitHub's usual JSON error are kept verbatim as the
+    /// message.
+    fn from_body(status: StatusCode, body: &str) -> UpstreamError {
+        match serde_json::from_str::<UpstreamErrorBody>(body) {
+            Ok(parsed) => UpstreamError {
+                status,
+                message: parsed.message,
+                documentation_url: parsed.documentation_url,
+                content_range: None,
+            },
+            Err(_) => UpstreamError {
+                status,
+                message: body.into(),
+                documentation_url: None,
+                content_range: None,
+            },
+        }
+    }
 }
 
 impl Display for GHAPIError {
@@ -21,86 +125,399 @@ impl Display for GHAPIError {
         match self {
             Self::ClientCreationFailed => write(f, format_args!("Creating reqwest client failed")),
             Self::RequestFailed => write(f, format_args!("Sending request failed")),
-            Self::ResponseUnsuccessful(msg) => {
-                write(f, format_args!("Request unsuccessful - {}", msg))
-            }
+            Self::ResponseUnsuccessful(err) => write(
+                f,
+                format_args!("Request unsuccessful - {} {}", err.status, err.message),
+            ),
             Self::FailedToDeserialize => write(f, format_args!("Failed to deserialize")),
+            Self::NotMergeable(err) => write(f, format_args!("Not mergeable - {}", err.message)),
+            Self::Conflict(err) => write(f, format_args!("Conflict - {}", err.message)),
+            Self::AuthenticationFailed => {
+                write(f, format_args!("Authenticating with GitHub failed"))
+            }
+            Self::RateLimited { reset_at } => {
+                write(f, format_args!("Rate limit exhausted until {}", reset_at))
+            }
         }
     }
 }
 
 impl Error for GHAPIError {}
 
+impl GHAPIError {
+    /// The error GitHub sent, if this error came from an upstream response.
+    pub fn upstream(&self) -> Option<&UpstreamError> {
+        match self {
+            Self::ResponseUnsuccessful(err) | Self::NotMergeable(err) | Self::Conflict(err) => {
+                Some(err)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Replaces an unsuccessful response with `status` by the more specific
+/// error `refine` builds from it. Other errors pass through unchanged.
+fn refine_status(
+    report: Report<GHAPIError>,
+    status: StatusCode,
+    refine: fn(UpstreamError) -> GHAPIError,
+) -> Report<GHAPIError> {
+    match report.current_context() {
+        GHAPIError::ResponseUnsuccessful(err) if err.status == status => {
+            let refined = refine(err.clone());
+            report.change_context(refined)
+        }
+        _ => report,
+    }
+}
+
 pub struct GithubAPI {
     base_url: String,
+    upload_url: String,
     client: Client,
+    rate_limit: Mutex<rate_limit::RateLimits>,
+    max_rate_limit_wait: Duration,
+    retry_policy: RetryPolicy,
+    cache: Option<Arc<dyn ResponseCache>>,
+    auth: auth::Auth,
 }
 
-fn default_headers(api_key: String) -> HeaderMap {
+fn default_headers() -> HeaderMap {
     let mut hm = HeaderMap::new();
-    let val = format!("token {}", api_key);
-    hm.insert(AUTHORIZATION, HeaderValue::from_str(&val).unwrap());
     hm.insert(USER_AGENT, HeaderValue::from_static("gh-api-service"));
     hm.insert(ACCEPT, HeaderValue::from_static("application/json"));
     hm
 }
 
+async fn unsuccessful_response(resp: Response) -> error_stack::Report<GHAPIError> {
+    let status = resp.status();
+    let content_range = resp
+        .headers()
+        .get(CONTENT_RANGE)
+        .and_then(|value| value.to_str().ok())
+        .map(String::from);
+    match resp.text().await {
+        Ok(body) => error_stack::Report::new(GHAPIError::ResponseUnsuccessful(UpstreamError {
+            content_range,
+            ..UpstreamError::from_body(status, &body)
+        })),
+        Err(err) => error_stack::Report::new(err).change_context(GHAPIError::FailedToDeserialize),
+    }
+}
+
+/// Settings for the underlying HTTP client.
+#[derive(Debug, Clone, Default)]
+pub struct ClientOptions {
+    pub request_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    /// Extra PEM encoded root certificates, e.g. an internal CA that signed
+    /// a GitHub Enterprise Server certificate.
+    pub root_certificates: Vec<Vec<u8>>,
+    pub client_identity: Option<ClientIdentity>,
+    /// Where release assets are uploaded. Derived from the base URL when
+    /// not set.
+    pub upload_url: Option<String>,
+}
+
+/// A TLS client certificate and key in a PKCS#12 archive.
+#[derive(Clone)]
+pub struct ClientIdentity {
+    pub pkcs12: Vec<u8>,
+    pub password: String,
+}
+
+impl std::fmt::Debug for ClientIdentity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ClientIdentity").finish_non_exhaustive()
+    }
+}
+
+/// The API and upload URLs of a GitHub Enterprise Server instance, given
+/// its root URL such as `https://github.example.com`.
+pub fn enterprise_urls(instance_url: &str) -> (String, String) {
+    let root = instance_url.trim_end_matches('/');
+    let root = root.strip_suffix(ENTERPRISE_API_PREFIX).unwrap_or(root);
+    (
+        format!("{}{}", root, ENTERPRISE_API_PREFIX),
+        format!("{}{}", root, ENTERPRISE_UPLOADS_PREFIX),
+    )
+}
+
+/// Appends `query` to `path`. Unset optional fields are left out.
+fn with_query<Q: Serialize>(path: &str, query: &Q) -> Result<String, GHAPIError> {
+    let query = serde_urlencoded::to_string(query)
+        .report()
+        .change_context(GHAPIError::RequestFailed)?;
+    if query.is_empty() {
+        return Ok(path.into());
+    }
+    Ok(format!("{}?{}", path, query))
+}
+
+fn default_upload_url(base_url: &str) -> String {
+    if base_url == BASE_URL {
+        return UPLOADS_URL.into();
+    }
+    match base_url.strip_suffix(ENTERPRISE_API_PREFIX) {
+        Some(root) => format!("{}{}", root, ENTERPRISE_UPLOADS_PREFIX),
+        None => base_url.into(),
+    }
+}
+
 impl GithubAPI {
+    /// Creates a client with default `ClientOptions`. The service itself
+    /// always configures options, so this is mostly for tests.
+    #[allow(dead_code)]
     pub fn new(api_key: String, base_url: Option<String>) -> Result<GithubAPI, GHAPIError> {
-        let client = reqwest::Client::builder()
-            .default_headers(default_headers(api_key))
+        Self::with_options(
+            Credentials::Token(api_key),
+            base_url,
+            ClientOptions::default(),
+        )
+    }
+
+    pub fn with_options(
+        credentials: Credentials,
+        base_url: Option<String>,
+        options: ClientOptions,
+    ) -> Result<GithubAPI, GHAPIError> {
+        let auth = auth::Auth::new(credentials)?;
+        let mut builder = reqwest::Client::builder().default_headers(default_headers());
+        if let Some(timeout) = options.request_timeout {
+            builder = builder.timeout(timeout);
+        }
+        if let Some(timeout) = options.connect_timeout {
+            builder = builder.connect_timeout(timeout);
+        }
+        for pem in &options.root_certificates {
+            let cert = Certificate::from_pem(pem)
+                .report()
+                .change_context(GHAPIError::ClientCreationFailed)
+                .attach_printable("invalid root certificate")?;
+            builder = builder.add_root_certificate(cert);
+        }
+        if let Some(identity) = &options.client_identity {
+            let identity = Identity::from_pkcs12_der(&identity.pkcs12, &identity.password)
+                .report()
+                .change_context(GHAPIError::ClientCreationFailed)
+                .attach_printable("invalid client identity")?;
+            builder = builder.identity(identity);
+        }
+        let client = builder
             .build()
             .report()
             .change_context(GHAPIError::ClientCreationFailed)?;
-        let url = base_url.unwrap_or_else(|| BASE_URL.into());
+        let base_url = base_url.unwrap_or_else(|| BASE_URL.into());
+        let base_url = base_url.trim_end_matches('/').to_string();
+        let upload_url = match options.upload_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None => default_upload_url(&base_url),
+        };
         Ok(GithubAPI {
             client,
-            base_url: url,
+            base_url,
+            upload_url,
+            rate_limit: Mutex::default(),
+            max_rate_limit_wait: Duration::ZERO,
+            retry_policy: RetryPolicy::default(),
+            cache: None,
+            auth,
         })
     }
 
-    pub async fn get_repository_details(&self, path: String) -> Result<Repository, GHAPIError> {
+    /// Enables conditional requests for GETs, backed by `cache`.
+    pub fn with_response_cache(mut self, cache: Arc<dyn ResponseCache>) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+
+    pub fn with_retry_policy(mut self, policy: RetryPolicy) -> Self {
+        self.retry_policy = policy;
+        self
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    pub fn upload_url(&self) -> &str {
+        &self.upload_url
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/{}", self.base_url, path.trim_start_matches('/'))
+    }
+
+    /// Sends the request, retrying transient failures according to the
+    /// retry policy. Every retried attempt is attached to the final error.
+    async fn send(&self, req: RequestBuilder) -> Result<Response, GHAPIError> {
+        Ok(self.send_as(req, None).await?.0)
+    }
+
+    /// Like `send`, but makes the first attempt with `credential` when one
+    /// is given. Also returns the hash of the credential that got the
+    /// response, which differs from `credential` after a retry with another
+    /// pooled token. GitHub App credentials are kept for the retries too.
+    async fn send_as(
+        &self,
+        req: RequestBuilder,
+        mut credential: Option<Credential>,
+    ) -> Result<(Response, u64), GHAPIError> {
+        let pinned = match self.auth {
+            Auth::App(_) => credential.clone(),
+            Auth::Pool(_) => None,
+        };
+        let mut req = req
+            .build()
+            .report()
+            .change_context(GHAPIError::RequestFailed)?;
+        let mut history = Vec::new();
+        let mut attempt = 1;
+        loop {
+            let method = req.method().clone();
+            let retry = req.try_clone();
+            let credential = credential.take().or_else(|| pinned.clone());
+            let report = match self.send_once(req, credential).await {
+                Ok(sent) => return Ok(sent),
+                Err(report) => report,
+            };
+            // A pooled token rejected with a 401 has just left the rotation,
+            // so the request is retried straight away with the next one.
+            let delay = match report.current_context() {
+                _ if report.contains::<token_pool::TokenRotatedOut>() => Some(Duration::ZERO),
+                err => self.retry_policy.delay_for(&method, err, attempt),
+            };
+            match (retry, delay) {
+                (Some(next), Some(delay)) => {
+                    tracing::warn!(
+                        "Attempt {} failed ({}), retrying in {:?}",
+                        attempt,
+                        report.current_context(),
+                        delay
+                    );
+                    history.push(retry::RetryAttempt {
+                        attempt,
+                        error: report.current_context().to_string(),
+                        delay,
+                    });
+                    tokio::time::sleep(delay).await;
+                    req = next;
+                    attempt += 1;
+                }
+                _ => {
+                    return Err(history
+                        .into_iter()
+                        .fold(report, |report, attempt| report.attach_printable(attempt)))
+                }
+            }
+        }
+    }
+
+    /// Sends the request once and turns any non-2xx response (other than a
+    /// 304 to a conditional request) into
+    /// `GHAPIError::ResponseUnsuccessful`, or `GHAPIError::RateLimited` when
+    /// GitHub rejected it for exceeding a rate limit.
+    async fn send_once(
+        &self,
+        mut req: Request,
+        credential: Option<Credential>,
+    ) -> Result<(Response, u64), GHAPIError> {
+        let credential = match credential {
+            Some(credential) => credential,
+            None => self.authorization(req.url()).await?,
+        };
+        self.wait_for_rate_limit(credential.hash).await?;
+        req.headers_mut().insert(AUTHORIZATION, credential.header);
         let resp = self
             .client
-            .get(format!("{}/repos/{}", self.base_url, path))
-            .send()
+            .execute(req)
             .await
             .report()
             .change_context(GHAPIError::RequestFailed)?;
-        if !resp.status().is_success() {
-            let body = resp
-                .text()
+        if let Some(reset_at) =
+            self.record_rate_limit(credential.hash, resp.status(), resp.headers())
+        {
+            return Err(unsuccessful_response(resp)
                 .await
-                .report()
-                .change_context(GHAPIError::FailedToDeserialize)?;
-            return Err(error_stack::Report::new(GHAPIError::ResponseUnsuccessful(
-                body,
-            )));
+                .change_context(GHAPIError::RateLimited { reset_at }));
         }
-        resp.json::<Repository>()
+        let rotated_out =
+            resp.status() == StatusCode::UNAUTHORIZED && self.reject_credential(credential.hash);
+        if !resp.status().is_success() && resp.status() != StatusCode::NOT_MODIFIED {
+            let report = unsuccessful_response(resp).await;
+            return Err(if rotated_out {
+                report.attach(token_pool::TokenRotatedOut)
+            } else {
+                report
+            });
+        }
+        Ok((resp, credential.hash))
+    }
+
+    async fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<T, GHAPIError> {
+        self.get_cached(self.url(path)).await?.json()
+    }
+
+    /// GETs `path` as `media_type`, e.g. a diff, rather than as JSON.
+    async fn get_text(&self, path: &str, media_type: &'static str) -> Result<String, GHAPIError> {
+        Ok(self
+            .get_cached_as(self.url(path), Some(media_type))
+            .await?
+            .text())
+    }
+
+    /// Sends `body` as JSON and deserializes the response.
+    async fn send_json<B: Serialize + ?Sized, T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: &B,
+    ) -> Result<T, GHAPIError> {
+        self.send_for_json(self.client.request(method, self.url(path)).json(body))
+            .await
+    }
+
+    /// Sends a prepared request and deserializes the JSON response.
+    async fn send_for_json<T: DeserializeOwned>(
+        &self,
+        req: RequestBuilder,
+    ) -> Result<T, GHAPIError> {
+        self.send(req)
+            .await?
+            .json()
             .await
             .report()
             .change_context(GHAPIError::FailedToDeserialize)
     }
-}
 
-#[derive(Debug, Deserialize)]
-pub struct Repository {
-    pub full_name: String,
-    pub description: Option<String>,
-    pub html_url: String,
+    pub async fn get_repository_details(&self, path: String) -> Result<Repository, GHAPIError> {
+        self.get_json(&format!("repos/{}", path)).await
+    }
+
+    pub async fn list_org_repositories(
+        &self,
+        org: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Repository>, GHAPIError> {
+        self.collect_paginated(&format!("orgs/{}/repos", org), per_page, limit)
+            .await
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::GithubAPI;
+    use super::{
+        enterprise_urls, ClientIdentity, ClientOptions, Credentials, GHAPIError, GithubAPI,
+    };
     use std::sync::Once;
     use wiremock::{
         matchers::{method, path},
         Mock, MockServer, ResponseTemplate,
     };
     static INIT: Once = Once::new();
-    static MOCK_BODY: &str = include_str!("mock_repo_details_body.json");
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
     fn setup() {
         INIT.call_once(|| {
             tracing_subscriber::fmt::init();
@@ -127,4 +544,64 @@ mod tests {
             .unwrap();
         assert_eq!("tarkalabs/ssh-signer", resp.full_name);
     }
+
+    #[tokio::test]
+    pub async fn test_enterprise_base_url() {
+        setup();
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/api/v3/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        let base_url = format!("{}/api/v3/", server.uri());
+        let gapi = GithubAPI::new("test-token".into(), Some(base_url)).unwrap();
+        assert_eq!(format!("{}/api/uploads", server.uri()), gapi.upload_url());
+        let resp = gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap();
+        assert_eq!("tarkalabs/ssh-signer", resp.full_name);
+    }
+
+    #[test]
+    fn test_enterprise_urls() {
+        let expected = (
+            "https://ghe.example.com/api/v3".to_string(),
+            "https://ghe.example.com/api/uploads".to_string(),
+        );
+        assert_eq!(expected, enterprise_urls("https://ghe.example.com/"));
+        assert_eq!(expected, enterprise_urls("https://ghe.example.com/api/v3"));
+        let gapi = GithubAPI::new("test-token".into(), None).unwrap();
+        assert_eq!("https://uploads.github.com", gapi.upload_url());
+    }
+
+    #[test]
+    fn test_custom_tls_options() {
+        let options = ClientOptions {
+            root_certificates: vec![include_bytes!("test_ca.pem").to_vec()],
+            ..ClientOptions::default()
+        };
+        assert!(
+            GithubAPI::with_options(Credentials::Token("test-token".into()), None, options).is_ok()
+        );
+        let options = ClientOptions {
+            client_identity: Some(ClientIdentity {
+                pkcs12: b"not a pkcs12 archive".to_vec(),
+                password: "secret".into(),
+            }),
+            ..ClientOptions::default()
+        };
+        let err = GithubAPI::with_options(Credentials::Token("test-token".into()), None, options)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err.current_context(),
+            GHAPIError::ClientCreationFailed
+        ));
+    }
 }
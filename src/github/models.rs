@@ -0,0 +1,163 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+/// A user or organization account.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct User {
+    pub login: String,
+    pub id: u64,
+    pub node_id: String,
+    pub avatar_url: String,
+    pub html_url: String,
+    /// `User`, `Organization` or `Bot`.
+    #[serde(rename = "type")]
+    pub account_type: String,
+    #[serde(default)]
+    pub site_admin: bool,
+}
+
+/// The author or committer recorded in a git object, which need not be a
+/// GitHub user.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitActor {
+    pub name: String,
+    pub email: String,
+    pub date: DateTime<Utc>,
+}
+
+/// The name and email to record as the author or committer of a commit
+/// made through the API. GitHub fills in the authenticated user when absent.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct CommitIdentity {
+    pub name: String,
+    pub email: String,
+    /// Defaults to the time of the request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// A file changed by a commit, comparison or pull request.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DiffEntry {
+    pub sha: String,
+    pub filename: String,
+    /// `added`, `removed`, `modified`, `renamed`, `copied`, `changed` or
+    /// `unchanged`.
+    pub status: String,
+    pub additions: u32,
+    pub deletions: u32,
+    pub changes: u32,
+    /// Missing for binary files and very large diffs.
+    pub patch: Option<String>,
+    pub previous_filename: Option<String>,
+    pub blob_url: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Visibility {
+    Public,
+    Private,
+    /// Visible to every member of the enterprise.
+    Internal,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct License {
+    pub key: String,
+    pub name: String,
+    pub spdx_id: Option<String>,
+    pub url: Option<String>,
+}
+
+/// What the authenticated credential may do with a repository.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Permissions {
+    pub admin: bool,
+    #[serde(default)]
+    pub maintain: bool,
+    pub push: bool,
+    #[serde(default)]
+    pub triage: bool,
+    pub pull: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Repository {
+    pub id: u64,
+    pub node_id: String,
+    pub name: String,
+    pub full_name: String,
+    pub owner: User,
+    pub private: bool,
+    /// Missing from some older GitHub Enterprise Server versions.
+    pub visibility: Option<Visibility>,
+    pub description: Option<String>,
+    pub html_url: String,
+    pub clone_url: String,
+    pub ssh_url: String,
+    pub homepage: Option<String>,
+    pub language: Option<String>,
+    pub default_branch: String,
+    #[serde(default)]
+    pub topics: Vec<String>,
+    pub license: Option<License>,
+    pub stargazers_count: u32,
+    pub watchers_count: u32,
+    pub forks_count: u32,
+    pub open_issues_count: u32,
+    /// Only returned when fetching a single repository.
+    pub subscribers_count: Option<u32>,
+    /// In kilobytes.
+    pub size: u64,
+    pub fork: bool,
+    pub archived: bool,
+    pub disabled: bool,
+    #[serde(default)]
+    pub is_template: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// `None` for repositories that were never pushed to.
+    pub pushed_at: Option<DateTime<Utc>>,
+    /// The repository this one was forked from.
+    pub parent: Option<Box<Repository>>,
+    /// The root of the fork network.
+    pub source: Option<Box<Repository>>,
+    pub permissions: Option<Permissions>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Repository, Visibility};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_repository_fixture() {
+        let repo: Repository =
+            serde_json::from_str(include_str!("fixtures/repository.json")).unwrap();
+        assert_eq!("tarkalabs", repo.owner.login);
+        assert_eq!("Organization", repo.owner.account_type);
+        assert_eq!(Some(Visibility::Public), repo.visibility);
+        assert_eq!("main", repo.default_branch);
+        assert_eq!(42, repo.stargazers_count);
+        assert_eq!(vec!["ssh", "signing", "rust"], repo.topics);
+        assert_eq!(Some("MIT"), repo.license.unwrap().spdx_id.as_deref());
+        assert_eq!(
+            Utc.with_ymd_and_hms(2020, 9, 17, 0, 57, 41).unwrap(),
+            repo.created_at
+        );
+        assert!(repo.permissions.unwrap().push);
+        assert!(repo.parent.is_none());
+    }
+
+    #[test]
+    fn test_fork_fixture() {
+        let repo: Repository =
+            serde_json::from_str(include_str!("fixtures/repository_fork.json")).unwrap();
+        assert!(repo.fork);
+        assert!(repo.license.is_none());
+        assert!(repo.pushed_at.is_none());
+        assert_eq!("tarkalabs/ssh-signer", repo.parent.unwrap().full_name);
+        assert_eq!("tarkalabs/ssh-signer", repo.source.unwrap().full_name);
+    }
+}
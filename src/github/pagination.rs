@@ -0,0 +1,267 @@
+use super::{auth::Credential, GHAPIError, GithubAPI};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use futures::{stream, Stream, StreamExt, TryStreamExt};
+use reqwest::{
+    header::{HeaderMap, LINK},
+    Url,
+};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// GitHub's default page size, used when callers have no preference.
+pub const DEFAULT_PER_PAGE: u8 = 30;
+/// The largest page size GitHub accepts.
+const MAX_PER_PAGE: u8 = 100;
+
+/// Extracts the `rel="next"` target from an RFC 5988 `Link` header.
+fn next_link(headers: &HeaderMap) -> Option<String> {
+    let link = headers.get(LINK)?.to_str().ok()?;
+    link.split(',').find_map(|part| {
+        let mut segments = part.split(';');
+        let target = segments.next()?.trim();
+        let is_next = segments.any(|param| {
+            let param = param.trim();
+            param == r#"rel="next""# || param == "rel=next"
+        });
+        if !is_next {
+            return None;
+        }
+        target
+            .strip_prefix('<')
+            .and_then(|t| t.strip_suffix('>'))
+            .map(String::from)
+    })
+}
+
+fn with_per_page(url: &str, per_page: u8) -> Result<String, GHAPIError> {
+    let mut url = Url::parse(url)
+        .report()
+        .change_context(GHAPIError::RequestFailed)?;
+    url.query_pairs_mut()
+        .append_pair("per_page", &per_page.to_string());
+    Ok(url.into())
+}
+
+/// The items of one page. Most list endpoints return a bare array, but some
+/// wrap it in an object next to a count, e.g.
+/// `{ "total_count": 2, "workflow_runs": [..] }`. Those name the field
+/// holding the items as `key`.
+fn page_items<T: DeserializeOwned>(body: Value, key: Option<&str>) -> Result<Vec<T>, GHAPIError> {
+    let items = match (body, key) {
+        (Value::Object(mut fields), Some(key)) => fields.remove(key).ok_or_else(|| {
+            Report::new(GHAPIError::FailedToDeserialize)
+                .attach_printable(format!("page has no `{}` field", key))
+        })?,
+        (body, _) => body,
+    };
+    serde_json::from_value(items)
+        .report()
+        .change_context(GHAPIError::FailedToDeserialize)
+}
+
+impl GithubAPI {
+    async fn get_page<T: DeserializeOwned>(
+        &self,
+        url: String,
+        key: Option<&str>,
+        per_page: Option<u8>,
+        credential: Option<Credential>,
+    ) -> Result<(Vec<T>, Option<String>), GHAPIError> {
+        let url = match per_page {
+            Some(per_page) => with_per_page(&url, per_page)?,
+            None => url,
+        };
+        let resp = self.get_cached_with(url, None, credential).await?;
+        let items = page_items(resp.json()?, key)?;
+        Ok((items, next_link(&resp.headers)))
+    }
+
+    /// Streams every item of a list endpoint, following `Link: rel="next"`
+    /// until GitHub stops sending one. `path` is relative to the base URL and
+    /// may carry its own query string.
+    pub fn paginate<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        path: &str,
+        per_page: u8,
+    ) -> impl Stream<Item = Result<T, GHAPIError>> + 'a {
+        self.paginate_wrapped(path, None, per_page)
+    }
+
+    /// Like `paginate`, for endpoints that wrap each page's items in the
+    /// field `key` of an object.
+    fn paginate_wrapped<'a, T: DeserializeOwned + 'a>(
+        &'a self,
+        path: &str,
+        key: Option<&'static str>,
+        per_page: u8,
+    ) -> impl Stream<Item = Result<T, GHAPIError>> + 'a {
+        let first = self.url(path);
+        let per_page = per_page.clamp(1, MAX_PER_PAGE);
+        stream::try_unfold(Some((first, true, None)), move |state| async move {
+            let (url, is_first, credential) = match state {
+                Some(state) => state,
+                None => return Ok::<_, Report<GHAPIError>>(None),
+            };
+            // Later pages come from the Link header, which already carries
+            // per_page and any filters from the first request, and are sent
+            // with the first page's credential.
+            let (per_page, credential) = if is_first {
+                (Some(per_page), self.listing_credential(&url).await?)
+            } else {
+                (None, credential)
+            };
+            let (items, next) = self
+                .get_page::<T>(url, key, per_page, credential.clone())
+                .await?;
+            Ok(Some((items, next.map(|url| (url, false, credential)))))
+        })
+        .map_ok(|items: Vec<T>| stream::iter(items.into_iter().map(Ok)))
+        .try_flatten()
+    }
+
+    /// Collects at most `limit` items of a list endpoint, fetching only as
+    /// many pages as needed.
+    pub async fn collect_paginated<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<T>, GHAPIError> {
+        self.paginate(path, per_page)
+            .take(limit)
+            .try_collect()
+            .await
+    }
+
+    /// Like `collect_paginated`, for endpoints that wrap each page's items
+    /// in the field `key` of an object.
+    pub(super) async fn collect_wrapped<T: DeserializeOwned>(
+        &self,
+        path: &str,
+        key: &'static str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<T>, GHAPIError> {
+        self.paginate_wrapped(path, Some(key), per_page)
+            .take(limit)
+            .try_collect()
+            .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_link, page_items};
+    use crate::github::GithubAPI;
+    use futures::TryStreamExt;
+    use reqwest::header::{HeaderMap, HeaderValue, LINK};
+    use serde::Deserialize;
+    use wiremock::{
+        matchers::{method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        id: u32,
+    }
+
+    async fn mount_pages(server: &MockServer) {
+        Mock::given(method("GET"))
+            .and(path("/orgs/tarkalabs/repos"))
+            .and(query_param("per_page", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "id": 1 }, { "id": 2 }]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{0}/organizations/7/repos?per_page=2&page=2>; rel="next", <{0}/organizations/7/repos?per_page=2&page=2>; rel="last""#,
+                            server.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/organizations/7/repos"))
+            .and(query_param("page", "2"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!([{ "id": 3 }]))
+                    .insert_header(
+                        "Link",
+                        format!(
+                            r#"<{0}/orgs/tarkalabs/repos?per_page=2>; rel="first", <{0}/orgs/tarkalabs/repos?per_page=2>; rel="prev""#,
+                            server.uri()
+                        )
+                        .as_str(),
+                    ),
+            )
+            .mount(server)
+            .await;
+    }
+
+    #[test]
+    fn test_next_link() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(
+                r#"<https://api.github.com/repositories/1/issues?page=2>; rel="next", <https://api.github.com/repositories/1/issues?page=5>; rel="last""#,
+            ),
+        );
+        assert_eq!(
+            Some("https://api.github.com/repositories/1/issues?page=2".to_string()),
+            next_link(&headers)
+        );
+        headers.insert(
+            LINK,
+            HeaderValue::from_static(r#"<https://api.github.com/x?page=1>; rel="prev""#),
+        );
+        assert_eq!(None, next_link(&headers));
+    }
+
+    #[test]
+    fn test_wrapped_page_items() {
+        let body = serde_json::json!({
+            "total_count": 2,
+            "pull_requests": [{ "id": 9 }],
+            "workflow_runs": [{ "id": 1 }, { "id": 2 }]
+        });
+        let items: Vec<Item> = page_items(body.clone(), Some("workflow_runs")).unwrap();
+        assert_eq!(vec![1, 2], items.iter().map(|i| i.id).collect::<Vec<_>>());
+        assert!(page_items::<Item>(body, Some("jobs")).is_err());
+        let items: Vec<Item> = page_items(serde_json::json!([{ "id": 3 }]), None).unwrap();
+        assert_eq!(3, items[0].id);
+    }
+
+    #[tokio::test]
+    async fn test_paginate_follows_next_link() {
+        let server = MockServer::start().await;
+        mount_pages(&server).await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let items: Vec<Item> = gapi
+            .paginate("orgs/tarkalabs/repos", 2)
+            .try_collect()
+            .await
+            .unwrap();
+        let ids: Vec<u32> = items.iter().map(|i| i.id).collect();
+        assert_eq!(vec![1, 2, 3], ids);
+    }
+
+    #[tokio::test]
+    async fn test_collect_paginated_stops_at_limit() {
+        let server = MockServer::start().await;
+        mount_pages(&server).await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let items: Vec<Item> = gapi
+            .collect_paginated("orgs/tarkalabs/repos", 2, 2)
+            .await
+            .unwrap();
+        assert_eq!(2, items.len());
+        let requests = server.received_requests().await.unwrap();
+        assert_eq!(1, requests.len());
+    }
+}
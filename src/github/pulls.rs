@@ -0,0 +1,401 @@
+use super::{
+    issues::{IssueState, Label, StateFilter},
+    models::{DiffEntry, Repository, User},
+    refine_status, with_query, GHAPIError, GithubAPI,
+};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use reqwest::{Method, StatusCode};
+use serde::{Deserialize, Serialize};
+
+/// One side of a pull request: the branch it merges from (`head`) or into
+/// (`base`).
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PullRequestRef {
+    /// `owner:branch`.
+    pub label: String,
+    #[serde(rename = "ref")]
+    pub branch: String,
+    pub sha: String,
+    /// `None` once the fork the head came from is deleted.
+    pub repo: Option<Box<Repository>>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PullRequest {
+    pub id: u64,
+    pub node_id: String,
+    pub number: u64,
+    pub title: String,
+    pub body: Option<String>,
+    pub state: IssueState,
+    #[serde(default)]
+    pub draft: bool,
+    pub user: User,
+    pub labels: Vec<Label>,
+    pub assignees: Vec<User>,
+    #[serde(default)]
+    pub requested_reviewers: Vec<User>,
+    pub head: PullRequestRef,
+    pub base: PullRequestRef,
+    pub html_url: String,
+    /// The fields below are only returned when fetching a single pull
+    /// request. `mergeable` stays `None` while GitHub computes it.
+    pub merged: Option<bool>,
+    pub mergeable: Option<bool>,
+    pub mergeable_state: Option<String>,
+    pub merge_commit_sha: Option<String>,
+    pub commits: Option<u32>,
+    pub additions: Option<u32>,
+    pub deletions: Option<u32>,
+    pub changed_files: Option<u32>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub closed_at: Option<DateTime<Utc>>,
+    pub merged_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewState {
+    Approved,
+    ChangesRequested,
+    Commented,
+    Dismissed,
+    Pending,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Review {
+    pub id: u64,
+    pub user: Option<User>,
+    pub body: String,
+    pub state: ReviewState,
+    pub commit_id: Option<String>,
+    pub html_url: String,
+    pub submitted_at: Option<DateTime<Utc>>,
+}
+
+/// A comment on a line of a pull request's diff.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReviewComment {
+    pub id: u64,
+    pub pull_request_review_id: Option<u64>,
+    pub in_reply_to_id: Option<u64>,
+    pub user: User,
+    pub body: String,
+    pub path: String,
+    pub line: Option<u32>,
+    pub side: Option<String>,
+    pub commit_id: String,
+    pub diff_hunk: String,
+    pub html_url: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Filters for listing a repository's pull requests.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct PullRequestFilter {
+    pub state: Option<StateFilter>,
+    /// `user:branch` of the head.
+    pub head: Option<String>,
+    /// The branch the pull requests merge into.
+    pub base: Option<String>,
+}
+
+/// What submitting a review does. Leaving it unset creates a pending
+/// review.
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ReviewEvent {
+    Approve,
+    RequestChanges,
+    Comment,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct DraftReviewComment {
+    pub path: String,
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    /// `LEFT` or `RIGHT` side of the diff.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NewReview {
+    /// Defaults to the head of the pull request.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<ReviewEvent>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub comments: Vec<DraftReviewComment>,
+}
+
+/// A review comment on a line, or a reply to another comment when
+/// `in_reply_to` is set, in which case the location fields are ignored.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewReviewComment {
+    pub body: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub line: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub side: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub in_reply_to: Option<u64>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReviewerRequest {
+    #[serde(default)]
+    pub reviewers: Vec<String>,
+    #[serde(default)]
+    pub team_reviewers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MergeMethod {
+    Merge,
+    Squash,
+    Rebase,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct MergeRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_title: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub commit_message: Option<String>,
+    /// The merge only happens if the head is still at this SHA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub merge_method: Option<MergeMethod>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MergeResult {
+    pub sha: String,
+    pub merged: bool,
+    pub message: String,
+}
+
+impl GithubAPI {
+    pub async fn list_pull_requests(
+        &self,
+        owner: &str,
+        repo: &str,
+        filter: &PullRequestFilter,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<PullRequest>, GHAPIError> {
+        let path = with_query(&format!("repos/{}/{}/pulls", owner, repo), filter)?;
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn get_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+    ) -> Result<PullRequest, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/pulls/{}", owner, repo, number))
+            .await
+    }
+
+    pub async fn list_pull_request_files(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<DiffEntry>, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/files", owner, repo, number);
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn list_reviews(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Review>, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/reviews", owner, repo, number);
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn create_review(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        review: &NewReview,
+    ) -> Result<Review, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/reviews", owner, repo, number);
+        self.send_json(Method::POST, &path, review).await
+    }
+
+    pub async fn list_review_comments(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<ReviewComment>, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/comments", owner, repo, number);
+        self.collect_paginated(&path, per_page, limit).await
+    }
+
+    pub async fn create_review_comment(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        comment: &NewReviewComment,
+    ) -> Result<ReviewComment, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/comments", owner, repo, number);
+        self.send_json(Method::POST, &path, comment).await
+    }
+
+    pub async fn request_reviewers(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        request: &ReviewerRequest,
+    ) -> Result<PullRequest, GHAPIError> {
+        let path = format!(
+            "repos/{}/{}/pulls/{}/requested_reviewers",
+            owner, repo, number
+        );
+        self.send_json(Method::POST, &path, request).await
+    }
+
+    /// Merges the pull request. Fails with `GHAPIError::NotMergeable` when
+    /// GitHub refuses the merge and with `GHAPIError::Conflict` when the
+    /// head moved past `merge.sha`.
+    pub async fn merge_pull_request(
+        &self,
+        owner: &str,
+        repo: &str,
+        number: u64,
+        merge: &MergeRequest,
+    ) -> Result<MergeResult, GHAPIError> {
+        let path = format!("repos/{}/{}/pulls/{}/merge", owner, repo, number);
+        self.send_json(Method::PUT, &path, merge)
+            .await
+            .map_err(|report| {
+                let report = refine_status(
+                    report,
+                    StatusCode::METHOD_NOT_ALLOWED,
+                    GHAPIError::NotMergeable,
+                );
+                refine_status(report, StatusCode::CONFLICT, GHAPIError::Conflict)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MergeMethod, MergeRequest};
+    use crate::github::{GHAPIError, GithubAPI};
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static PULL: &str = include_str!("fixtures/pull_request.json");
+    static FILES: &str = include_str!("fixtures/pull_request_files.json");
+
+    fn json_response(status: u16, body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(status)
+            .set_body_string(body)
+            .insert_header("Content-Type", "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_get_pull_request_and_files() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/18"))
+            .respond_with(json_response(200, PULL))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/18/files"))
+            .respond_with(json_response(200, FILES))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let pull = gapi
+            .get_pull_request("tarkalabs", "ssh-signer", 18)
+            .await
+            .unwrap();
+        assert_eq!("fix-ed25519", pull.head.branch);
+        assert_eq!("main", pull.base.branch);
+        assert_eq!(Some(true), pull.mergeable);
+        let files = gapi
+            .list_pull_request_files("tarkalabs", "ssh-signer", 18, 30, 10)
+            .await
+            .unwrap();
+        assert_eq!(2, files.len());
+        assert_eq!(12, files[0].additions);
+        assert!(files[0].patch.is_some());
+        assert!(files[1].patch.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_merge_errors_are_typed() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/18/merge"))
+            .and(body_json(serde_json::json!({
+                "sha": "stale",
+                "merge_method": "squash"
+            })))
+            .respond_with(json_response(
+                409,
+                r#"{ "message": "Head branch was modified. Review and try the merge again." }"#,
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/19/merge"))
+            .respond_with(json_response(
+                405,
+                r#"{ "message": "Pull Request is not mergeable" }"#,
+            ))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let merge = MergeRequest {
+            sha: Some("stale".into()),
+            merge_method: Some(MergeMethod::Squash),
+            ..MergeRequest::default()
+        };
+        let err = gapi
+            .merge_pull_request("tarkalabs", "ssh-signer", 18, &merge)
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), GHAPIError::Conflict(_)));
+        let err = gapi
+            .merge_pull_request("tarkalabs", "ssh-signer", 19, &MergeRequest::default())
+            .await
+            .unwrap_err();
+        assert!(matches!(err.current_context(), GHAPIError::NotMergeable(_)));
+    }
+}
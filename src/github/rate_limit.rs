@@ -0,0 +1,255 @@
+use super::{GHAPIError, GithubAPI};
+use error_stack::{Report, Result};
+use reqwest::{
+    header::{HeaderMap, RETRY_AFTER},
+    StatusCode,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The rate-limit budget GitHub last reported for the token, taken from the
+/// `X-RateLimit-*` response headers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RateLimit {
+    pub limit: u32,
+    pub remaining: u32,
+    pub used: u32,
+    /// Unix timestamp (seconds) at which the budget resets.
+    pub reset_at: u64,
+    pub resource: Option<String>,
+}
+
+/// Rate-limit state per credential, keyed by its hash.
+#[derive(Debug, Default)]
+pub(super) struct RateLimits {
+    /// The credential GitHub most recently reported a budget for.
+    latest: Option<u64>,
+    states: HashMap<u64, RateLimitState>,
+}
+
+#[derive(Debug, Default)]
+struct RateLimitState {
+    latest: Option<RateLimit>,
+    /// Set by a secondary rate limit; no requests go out before this time.
+    blocked_until: Option<u64>,
+}
+
+impl RateLimitState {
+    /// The time requests may resume, if the budget is exhausted right now.
+    fn exhausted_until(&self, now: u64) -> Option<u64> {
+        let primary = self
+            .latest
+            .as_ref()
+            .filter(|rl| rl.remaining == 0 && rl.reset_at > now)
+            .map(|rl| rl.reset_at);
+        let secondary = self.blocked_until.filter(|until| *until > now);
+        primary.max(secondary)
+    }
+}
+
+pub(super) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+fn header<T: std::str::FromStr>(headers: &HeaderMap, name: &str) -> Option<T> {
+    headers.get(name)?.to_str().ok()?.parse().ok()
+}
+
+fn parse_rate_limit(headers: &HeaderMap) -> Option<RateLimit> {
+    let limit = header(headers, "x-ratelimit-limit")?;
+    let remaining = header(headers, "x-ratelimit-remaining")?;
+    let reset_at = header(headers, "x-ratelimit-reset")?;
+    Some(RateLimit {
+        limit,
+        remaining,
+        used: header(headers, "x-ratelimit-used").unwrap_or(limit.saturating_sub(remaining)),
+        reset_at,
+        resource: header(headers, "x-ratelimit-resource"),
+    })
+}
+
+impl GithubAPI {
+    /// Caps how long a request waits for an exhausted budget to reset before
+    /// failing with `GHAPIError::RateLimited` instead. Defaults to zero.
+    pub fn with_max_rate_limit_wait(mut self, wait: Duration) -> Self {
+        self.max_rate_limit_wait = wait;
+        self
+    }
+
+    /// The most recent rate-limit state GitHub reported, for whichever
+    /// credential was used last.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        let limits = self.rate_limit.lock().unwrap();
+        limits
+            .latest
+            .and_then(|credential| limits.states.get(&credential)?.latest.clone())
+    }
+
+    pub(super) fn rate_limit_for(&self, credential: u64) -> Option<RateLimit> {
+        let limits = self.rate_limit.lock().unwrap();
+        limits.states.get(&credential)?.latest.clone()
+    }
+
+    pub(super) fn exhausted_until(&self, credential: u64, now: u64) -> Option<u64> {
+        let limits = self.rate_limit.lock().unwrap();
+        limits.states.get(&credential)?.exhausted_until(now)
+    }
+
+    /// Queries `/rate_limit`, which does not count against the budget, so
+    /// that `rate_limit()` is populated even before any other call.
+    pub async fn refresh_rate_limit(&self) -> Result<Option<RateLimit>, GHAPIError> {
+        self.send(self.client.get(self.url("rate_limit"))).await?;
+        Ok(self.rate_limit())
+    }
+
+    /// Waits for the budget to reset if that happens within the configured
+    /// maximum wait, otherwise fails fast.
+    pub(super) async fn wait_for_rate_limit(&self, credential: u64) -> Result<(), GHAPIError> {
+        let now = now();
+        if let Some(reset_at) = self.exhausted_until(credential, now) {
+            let wait = Duration::from_secs(reset_at - now);
+            if wait > self.max_rate_limit_wait {
+                return Err(Report::new(GHAPIError::RateLimited { reset_at }));
+            }
+            tracing::debug!("Rate limit exhausted, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
+        }
+        Ok(())
+    }
+
+    /// Records the rate-limit headers of a response sent with `credential`.
+    /// Returns the reset time when the response itself was rejected by a
+    /// primary or secondary rate limit.
+    pub(super) fn record_rate_limit(
+        &self,
+        credential: u64,
+        status: StatusCode,
+        headers: &HeaderMap,
+    ) -> Option<u64> {
+        let mut limits = self.rate_limit.lock().unwrap();
+        let parsed = parse_rate_limit(headers);
+        if parsed.is_some() {
+            limits.latest = Some(credential);
+        }
+        let state = limits.states.entry(credential).or_default();
+        if let Some(rl) = &parsed {
+            state.latest = Some(rl.clone());
+        }
+        if status != StatusCode::FORBIDDEN && status != StatusCode::TOO_MANY_REQUESTS {
+            return None;
+        }
+        if let Some(retry_after) = header::<u64>(headers, RETRY_AFTER.as_str()) {
+            let until = now() + retry_after;
+            state.blocked_until = Some(until);
+            return Some(until);
+        }
+        parsed.filter(|rl| rl.remaining == 0).map(|rl| rl.reset_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::now;
+    use crate::github::{GHAPIError, GithubAPI};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
+
+    #[tokio::test]
+    async fn test_rate_limit_headers_are_tracked() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json")
+                    .insert_header("X-RateLimit-Limit", "5000")
+                    .insert_header("X-RateLimit-Remaining", "4990")
+                    .insert_header("X-RateLimit-Used", "10")
+                    .insert_header("X-RateLimit-Reset", "1700000000")
+                    .insert_header("X-RateLimit-Resource", "core"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        assert!(gapi.rate_limit().is_none());
+        gapi.get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap();
+        let rl = gapi.rate_limit().unwrap();
+        assert_eq!(5000, rl.limit);
+        assert_eq!(4990, rl.remaining);
+        assert_eq!(1700000000, rl.reset_at);
+        assert_eq!(Some("core".to_string()), rl.resource);
+    }
+
+    #[tokio::test]
+    async fn test_exhausted_budget_fails_fast() {
+        let server = MockServer::start().await;
+        let reset_at = now() + 600;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .set_body_json(serde_json::json!({ "message": "API rate limit exceeded" }))
+                    .insert_header("X-RateLimit-Limit", "5000")
+                    .insert_header("X-RateLimit-Remaining", "0")
+                    .insert_header("X-RateLimit-Reset", reset_at.to_string().as_str()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        for _ in 0..2 {
+            let err = gapi
+                .get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap_err();
+            match err.current_context() {
+                GHAPIError::RateLimited { reset_at: at } => assert_eq!(reset_at, *at),
+                other => panic!("unexpected error {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_secondary_rate_limit_uses_retry_after() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(403)
+                    .set_body_json(serde_json::json!({
+                        "message": "You have exceeded a secondary rate limit."
+                    }))
+                    .insert_header("Retry-After", "60"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let err = gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            GHAPIError::RateLimited { reset_at } if *reset_at >= now() + 59
+        ));
+        let err = gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            GHAPIError::RateLimited { .. }
+        ));
+    }
+}
@@ -0,0 +1,339 @@
+use super::{models::User, stream::ByteStream, with_query, GHAPIError, GithubAPI};
+use chrono::{DateTime, Utc};
+use error_stack::Result;
+use reqwest::{
+    header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+    Body, Method,
+};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ReleaseAsset {
+    pub id: u64,
+    pub node_id: String,
+    pub name: String,
+    pub label: Option<String>,
+    pub content_type: String,
+    /// `uploaded`, or `open` while an upload is in progress.
+    pub state: String,
+    pub size: u64,
+    pub download_count: u64,
+    pub browser_download_url: String,
+    pub uploader: Option<User>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Release {
+    pub id: u64,
+    pub node_id: String,
+    pub tag_name: String,
+    pub target_commitish: String,
+    pub name: Option<String>,
+    pub body: Option<String>,
+    pub draft: bool,
+    pub prerelease: bool,
+    pub author: User,
+    pub html_url: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` for drafts.
+    pub published_at: Option<DateTime<Utc>>,
+    pub assets: Vec<ReleaseAsset>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct NewRelease {
+    pub tag_name: String,
+    /// The branch or SHA to tag if `tag_name` does not exist yet. Defaults
+    /// to the default branch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_commitish: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(default)]
+    pub draft: bool,
+    #[serde(default)]
+    pub prerelease: bool,
+    /// Lets GitHub write the notes from merged pull requests.
+    #[serde(default)]
+    pub generate_release_notes: bool,
+}
+
+/// Changes to a release. Fields left as `None` are not changed.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ReleaseUpdate {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tag_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_commitish: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub draft: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prerelease: Option<bool>,
+}
+
+/// Describes an asset to upload. GitHub needs the length up front, so the
+/// body cannot be sent chunked.
+#[derive(Debug, Clone, Serialize)]
+pub struct AssetUpload {
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    #[serde(skip)]
+    pub content_type: String,
+    #[serde(skip)]
+    pub content_length: u64,
+}
+
+impl GithubAPI {
+    pub async fn list_releases(
+        &self,
+        owner: &str,
+        repo: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Release>, GHAPIError> {
+        self.collect_paginated(
+            &format!("repos/{}/{}/releases", owner, repo),
+            per_page,
+            limit,
+        )
+        .await
+    }
+
+    /// The most recent published release that is not a prerelease.
+    pub async fn get_latest_release(&self, owner: &str, repo: &str) -> Result<Release, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/releases/latest", owner, repo))
+            .await
+    }
+
+    pub async fn get_release_by_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &str,
+    ) -> Result<Release, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/releases/tags/{}", owner, repo, tag))
+            .await
+    }
+
+    pub async fn create_release(
+        &self,
+        owner: &str,
+        repo: &str,
+        release: &NewRelease,
+    ) -> Result<Release, GHAPIError> {
+        let path = format!("repos/{}/{}/releases", owner, repo);
+        self.send_json(Method::POST, &path, release).await
+    }
+
+    pub async fn update_release(
+        &self,
+        owner: &str,
+        repo: &str,
+        id: u64,
+        update: &ReleaseUpdate,
+    ) -> Result<Release, GHAPIError> {
+        let path = format!("repos/{}/{}/releases/{}", owner, repo, id);
+        self.send_json(Method::PATCH, &path, update).await
+    }
+
+    /// Deletes the release but not its tag.
+    pub async fn delete_release(&self, owner: &str, repo: &str, id: u64) -> Result<(), GHAPIError> {
+        let path = format!("repos/{}/{}/releases/{}", owner, repo, id);
+        self.send(self.client.delete(self.url(&path))).await?;
+        Ok(())
+    }
+
+    /// Uploads `body` as an asset of release `id` to the uploads host
+    /// without buffering it. A streamed body cannot be replayed, so the
+    /// upload is not retried.
+    pub async fn upload_release_asset(
+        &self,
+        owner: &str,
+        repo: &str,
+        id: u64,
+        asset: &AssetUpload,
+        body: impl Into<Body>,
+    ) -> Result<ReleaseAsset, GHAPIError> {
+        let path = with_query(
+            &format!("repos/{}/{}/releases/{}/assets", owner, repo, id),
+            asset,
+        )?;
+        let req = self
+            .client
+            .post(format!("{}/{}", self.upload_url, path))
+            .header(CONTENT_TYPE, &asset.content_type)
+            .header(CONTENT_LENGTH, asset.content_length)
+            .body(body);
+        self.send_for_json(req).await
+    }
+
+    /// Streams an asset's bytes. GitHub redirects to a storage host, which
+    /// is followed without passing on the credentials.
+    pub async fn download_release_asset(
+        &self,
+        owner: &str,
+        repo: &str,
+        asset_id: u64,
+    ) -> Result<ByteStream, GHAPIError> {
+        let url = self.url(&format!(
+            "repos/{}/{}/releases/assets/{}",
+            owner, repo, asset_id
+        ));
+        let resp = self
+            .send(
+                self.client
+                    .get(url)
+                    .header(ACCEPT, "application/octet-stream"),
+            )
+            .await?;
+        Ok(ByteStream::from_response(resp))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AssetUpload, NewRelease};
+    use crate::github::{ClientOptions, Credentials, GithubAPI};
+    use futures::StreamExt;
+    use wiremock::{
+        matchers::{body_bytes, body_partial_json, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static RELEASE: &str = include_str!("fixtures/release.json");
+
+    fn json_response(body: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_string(body)
+            .insert_header("Content-Type", "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_release_by_tag_and_create() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/releases/tags/v0.2.1"))
+            .respond_with(json_response(RELEASE))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/releases"))
+            .and(body_partial_json(serde_json::json!({
+                "tag_name": "v0.2.1",
+                "draft": true,
+                "generate_release_notes": true
+            })))
+            .respond_with(
+                ResponseTemplate::new(201)
+                    .set_body_string(RELEASE)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let release = gapi
+            .get_release_by_tag("tarkalabs", "ssh-signer", "v0.2.1")
+            .await
+            .unwrap();
+        assert_eq!("v0.2.1", release.tag_name);
+        assert_eq!("ssh-signer-x86_64-linux.tar.gz", release.assets[0].name);
+        let new = NewRelease {
+            tag_name: "v0.2.1".into(),
+            draft: true,
+            generate_release_notes: true,
+            ..NewRelease::default()
+        };
+        gapi.create_release("tarkalabs", "ssh-signer", &new)
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_upload_streams_to_uploads_host() {
+        let server = MockServer::start().await;
+        let uploads = MockServer::start().await;
+        let asset: serde_json::Value =
+            serde_json::from_str::<serde_json::Value>(RELEASE).unwrap()["assets"][0].clone();
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/releases/7102447/assets"))
+            .and(query_param("name", "ssh-signer-x86_64-linux.tar.gz"))
+            .and(header("Content-Type", "application/gzip"))
+            .and(header("Content-Length", "11"))
+            .and(body_bytes(b"hello world".to_vec()))
+            .respond_with(ResponseTemplate::new(201).set_body_json(asset))
+            .expect(1)
+            .mount(&uploads)
+            .await;
+        let options = ClientOptions {
+            upload_url: Some(uploads.uri()),
+            ..ClientOptions::default()
+        };
+        let gapi = GithubAPI::with_options(
+            Credentials::Token("test-token".into()),
+            Some(server.uri()),
+            options,
+        )
+        .unwrap();
+        let chunks: Vec<Result<&'static [u8], std::io::Error>> = vec![Ok(b"hello "), Ok(b"world")];
+        let upload = AssetUpload {
+            name: "ssh-signer-x86_64-linux.tar.gz".into(),
+            label: None,
+            content_type: "application/gzip".into(),
+            content_length: 11,
+        };
+        let asset = gapi
+            .upload_release_asset(
+                "tarkalabs",
+                "ssh-signer",
+                7102447,
+                &upload,
+                reqwest::Body::wrap_stream(futures::stream::iter(chunks)),
+            )
+            .await
+            .unwrap();
+        assert_eq!(1083624, asset.id);
+    }
+
+    #[tokio::test]
+    async fn test_download_follows_redirect() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/releases/assets/1083624"))
+            .and(header("Accept", "application/octet-stream"))
+            .respond_with(ResponseTemplate::new(302).insert_header(
+                "Location",
+                format!("{}/storage/1083624", server.uri()).as_str(),
+            ))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/storage/1083624"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(b"tarball".to_vec())
+                    .insert_header("Content-Type", "application/octet-stream"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let mut stream = gapi
+            .download_release_asset("tarkalabs", "ssh-signer", 1083624)
+            .await
+            .unwrap();
+        assert_eq!(Some(7), stream.content_length);
+        let mut body = Vec::new();
+        while let Some(chunk) = stream.body.next().await {
+            body.extend_from_slice(&chunk.unwrap());
+        }
+        assert_eq!(b"tarball".to_vec(), body);
+    }
+}
@@ -0,0 +1,202 @@
+use super::{rate_limit::now, GHAPIError};
+use rand::Rng;
+use reqwest::{Method, StatusCode};
+use std::fmt::{self, Display};
+use std::time::Duration;
+
+/// How `GithubAPI` retries requests that failed for transient reasons.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Total attempts, including the first one. `1` disables retries.
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(200),
+            max_delay: Duration::from_secs(5),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Exponential backoff with full jitter: a random delay between zero and
+    /// `base_delay * 2^(attempt - 1)`, capped at `max_delay`.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let exp = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+        let ceiling = exp.min(self.max_delay);
+        rand::thread_rng().gen_range(Duration::ZERO..=ceiling)
+    }
+
+    /// The delay before retrying after `attempt` failed with `err`, or
+    /// `None` if the request should not be retried.
+    pub(super) fn delay_for(
+        &self,
+        method: &Method,
+        err: &GHAPIError,
+        attempt: u32,
+    ) -> Option<Duration> {
+        if attempt >= self.max_attempts || !is_idempotent(method) {
+            return None;
+        }
+        match err {
+            GHAPIError::RequestFailed => Some(self.backoff(attempt)),
+            GHAPIError::ResponseUnsuccessful(upstream)
+                if is_retryable_status(upstream.status, &upstream.message) =>
+            {
+                Some(self.backoff(attempt))
+            }
+            // Worth waiting for only if the reset falls within our cap.
+            GHAPIError::RateLimited { reset_at } => {
+                let wait = Duration::from_secs(reset_at.saturating_sub(now()));
+                (wait <= self.max_delay).then(|| wait.max(self.backoff(attempt)))
+            }
+            _ => None,
+        }
+    }
+}
+
+fn is_idempotent(method: &Method) -> bool {
+    matches!(
+        *method,
+        Method::GET | Method::HEAD | Method::OPTIONS | Method::PUT | Method::DELETE
+    )
+}
+
+fn is_retryable_status(status: StatusCode, message: &str) -> bool {
+    if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+        return true;
+    }
+    // Abuse detection and secondary rate limits come back as 403s.
+    let message = message.to_lowercase();
+    status == StatusCode::FORBIDDEN
+        && (message.contains("abuse") || message.contains("secondary rate limit"))
+}
+
+/// A failed attempt that was retried, attached to the report of the final
+/// error so the whole retry history is visible.
+#[derive(Debug)]
+pub struct RetryAttempt {
+    pub attempt: u32,
+    pub error: String,
+    pub delay: Duration,
+}
+
+impl Display for RetryAttempt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "attempt {} failed ({}), retried after {:?}",
+            self.attempt, self.error, self.delay
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{RetryAttempt, RetryPolicy};
+    use crate::github::{GHAPIError, GithubAPI};
+    use std::time::Duration;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
+
+    fn fast_policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(10),
+        }
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let policy = fast_policy();
+        for attempt in 1..10 {
+            assert!(policy.backoff(attempt) <= policy.max_delay);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_failures() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(ResponseTemplate::new(502))
+            .up_to_n_times(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri()))
+            .unwrap()
+            .with_retry_policy(fast_policy());
+        let resp = gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap();
+        assert_eq!("tarkalabs/ssh-signer", resp.full_name);
+        assert_eq!(2, server.received_requests().await.unwrap().len());
+    }
+
+    #[tokio::test]
+    async fn test_retry_history_is_attached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri()))
+            .unwrap()
+            .with_retry_policy(fast_policy());
+        let err = gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.current_context(),
+            GHAPIError::ResponseUnsuccessful(upstream) if upstream.status == 503
+        ));
+        let attempts: Vec<u32> = err
+            .frames()
+            .filter_map(|frame| frame.downcast_ref::<RetryAttempt>())
+            .map(|a| a.attempt)
+            .collect();
+        assert_eq!(2, attempts.len());
+    }
+
+    #[tokio::test]
+    async fn test_non_idempotent_requests_are_not_retried() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/issues"))
+            .respond_with(ResponseTemplate::new(502))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri()))
+            .unwrap()
+            .with_retry_policy(fast_policy());
+        let req = gapi
+            .client
+            .post(gapi.url("repos/tarkalabs/ssh-signer/issues"));
+        assert!(gapi.send(req).await.is_err());
+    }
+}
@@ -0,0 +1,409 @@
+use super::{GHAPIError, GithubAPI};
+use chrono::{DateTime, Utc};
+use crypto_box::aead::OsRng;
+use error_stack::{Report, Result};
+use futures::{stream, StreamExt};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+/// How many repositories a bulk secret update writes to at once.
+const BULK_CONCURRENCY: usize = 8;
+
+/// Where Actions secrets and variables live.
+#[derive(Debug, Clone, Copy)]
+pub enum SecretScope<'a> {
+    Repo {
+        owner: &'a str,
+        repo: &'a str,
+    },
+    Org(&'a str),
+    Environment {
+        owner: &'a str,
+        repo: &'a str,
+        environment: &'a str,
+    },
+}
+
+impl SecretScope<'_> {
+    /// The API path of the scope's `secrets` or `variables`.
+    pub(crate) fn path(&self, kind: &str) -> String {
+        match self {
+            SecretScope::Repo { owner, repo } => {
+                format!("repos/{}/{}/actions/{}", owner, repo, kind)
+            }
+            SecretScope::Org(org) => format!("orgs/{}/actions/{}", org, kind),
+            SecretScope::Environment {
+                owner,
+                repo,
+                environment,
+            } => format!(
+                "repos/{}/{}/environments/{}/{}",
+                owner, repo, environment, kind
+            ),
+        }
+    }
+}
+
+/// The key secrets of a scope must be encrypted with.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PublicKey {
+    pub key_id: String,
+    /// A base64 encoded X25519 public key.
+    pub key: String,
+}
+
+impl PublicKey {
+    /// Encrypts `value` into a libsodium sealed box, base64 encoded as
+    /// GitHub expects it.
+    fn seal(&self, value: &[u8]) -> Result<String, GHAPIError> {
+        let invalid_key = || {
+            Report::new(GHAPIError::FailedToDeserialize)
+                .attach_printable(format!("invalid public key {}", self.key_id))
+        };
+        let key: [u8; crypto_box::KEY_SIZE] = base64::decode(&self.key)
+            .map_err(|_| invalid_key())?
+            .try_into()
+            .map_err(|_| invalid_key())?;
+        let sealed = crypto_box::PublicKey::from(key)
+            .seal(&mut OsRng, value)
+            .map_err(|_| invalid_key())?;
+        Ok(base64::encode(sealed))
+    }
+}
+
+/// Which of an organization's repositories may use a secret or variable.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Visibility {
+    All,
+    Private,
+    Selected,
+}
+
+/// A secret's metadata. GitHub never returns secret values.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Secret {
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Only set for organization secrets.
+    pub visibility: Option<Visibility>,
+}
+
+/// A secret value to store. The value is encrypted before it leaves the
+/// service and never shows up in `Debug` output.
+#[derive(Clone, Deserialize)]
+pub struct SecretValue {
+    pub value: String,
+    /// Required for organization secrets, ignored otherwise.
+    pub visibility: Option<Visibility>,
+    /// The repositories that may use a `selected` organization secret.
+    pub selected_repository_ids: Option<Vec<u64>>,
+}
+
+impl std::fmt::Debug for SecretValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SecretValue")
+            .field("visibility", &self.visibility)
+            .field("selected_repository_ids", &self.selected_repository_ids)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Serialize)]
+struct EncryptedSecret<'a> {
+    encrypted_value: String,
+    key_id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    visibility: Option<Visibility>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    selected_repository_ids: Option<&'a [u64]>,
+}
+
+/// Whether storing a secret created it or replaced an existing value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretWrite {
+    Created,
+    Updated,
+}
+
+/// The outcome of a bulk secret update for one repository.
+#[derive(Debug, Clone, Serialize)]
+pub struct SecretOutcome {
+    pub repository: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<SecretWrite>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Variable {
+    pub name: String,
+    pub value: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// Only set for organization variables.
+    pub visibility: Option<Visibility>,
+}
+
+/// A variable to create, or the new state of one. Updating with a
+/// different `name` renames the variable.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewVariable {
+    pub name: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub visibility: Option<Visibility>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_repository_ids: Option<Vec<u64>>,
+}
+
+impl GithubAPI {
+    pub async fn get_secrets_public_key(
+        &self,
+        scope: SecretScope<'_>,
+    ) -> Result<PublicKey, GHAPIError> {
+        self.get_json(&format!("{}/public-key", scope.path("secrets")))
+            .await
+    }
+
+    pub async fn list_secrets(
+        &self,
+        scope: SecretScope<'_>,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Secret>, GHAPIError> {
+        self.collect_wrapped(&scope.path("secrets"), "secrets", per_page, limit)
+            .await
+    }
+
+    pub async fn get_secret(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+    ) -> Result<Secret, GHAPIError> {
+        self.get_json(&format!("{}/{}", scope.path("secrets"), name))
+            .await
+    }
+
+    /// Encrypts `secret` with the scope's public key and stores it as
+    /// `name`.
+    pub async fn set_secret(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+        secret: &SecretValue,
+    ) -> Result<SecretWrite, GHAPIError> {
+        let key = self.get_secrets_public_key(scope).await?;
+        let body = EncryptedSecret {
+            encrypted_value: key.seal(secret.value.as_bytes())?,
+            key_id: &key.key_id,
+            visibility: secret.visibility,
+            selected_repository_ids: secret.selected_repository_ids.as_deref(),
+        };
+        let path = format!("{}/{}", scope.path("secrets"), name);
+        let resp = self
+            .send(self.client.put(self.url(&path)).json(&body))
+            .await?;
+        Ok(match resp.status() {
+            StatusCode::CREATED => SecretWrite::Created,
+            _ => SecretWrite::Updated,
+        })
+    }
+
+    pub async fn delete_secret(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+    ) -> Result<(), GHAPIError> {
+        let path = format!("{}/{}", scope.path("secrets"), name);
+        self.send(self.client.delete(self.url(&path))).await?;
+        Ok(())
+    }
+
+    /// Stores `value` as the repository secret `name` on each of `owner`'s
+    /// `repos`, each encrypted with its repository's key. A failure only
+    /// affects its own repository.
+    pub async fn set_secret_on_repos(
+        &self,
+        owner: &str,
+        repos: &[String],
+        name: &str,
+        value: &str,
+    ) -> Vec<SecretOutcome> {
+        let secret = &SecretValue {
+            value: value.into(),
+            visibility: None,
+            selected_repository_ids: None,
+        };
+        stream::iter(repos.to_vec())
+            .map(|repo| async move {
+                let scope = SecretScope::Repo { owner, repo: &repo };
+                let (result, error) = match self.set_secret(scope, name, secret).await {
+                    Ok(write) => (Some(write), None),
+                    Err(report) => (None, Some(report.current_context().to_string())),
+                };
+                SecretOutcome {
+                    repository: format!("{}/{}", owner, repo),
+                    result,
+                    error,
+                }
+            })
+            .buffered(BULK_CONCURRENCY)
+            .collect()
+            .await
+    }
+
+    pub async fn list_variables(
+        &self,
+        scope: SecretScope<'_>,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Variable>, GHAPIError> {
+        self.collect_wrapped(&scope.path("variables"), "variables", per_page, limit)
+            .await
+    }
+
+    pub async fn get_variable(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+    ) -> Result<Variable, GHAPIError> {
+        self.get_json(&format!("{}/{}", scope.path("variables"), name))
+            .await
+    }
+
+    pub async fn create_variable(
+        &self,
+        scope: SecretScope<'_>,
+        variable: &NewVariable,
+    ) -> Result<(), GHAPIError> {
+        let path = scope.path("variables");
+        self.send(self.client.post(self.url(&path)).json(variable))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_variable(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+        variable: &NewVariable,
+    ) -> Result<(), GHAPIError> {
+        let path = format!("{}/{}", scope.path("variables"), name);
+        self.send(self.client.patch(self.url(&path)).json(variable))
+            .await?;
+        Ok(())
+    }
+
+    pub async fn delete_variable(
+        &self,
+        scope: SecretScope<'_>,
+        name: &str,
+    ) -> Result<(), GHAPIError> {
+        let path = format!("{}/{}", scope.path("variables"), name);
+        self.send(self.client.delete(self.url(&path))).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SecretScope, SecretValue, SecretWrite};
+    use crate::github::GithubAPI;
+    use crypto_box::{aead::OsRng, SecretKey};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    fn secret(value: &str) -> SecretValue {
+        SecretValue {
+            value: value.into(),
+            visibility: None,
+            selected_repository_ids: None,
+        }
+    }
+
+    async fn mount_public_key(server: &MockServer, repo: &str, key: &SecretKey) {
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/tarkalabs/{}/actions/secrets/public-key",
+                repo
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "key_id": "568250167242549743",
+                "key": base64::encode(key.public_key().as_bytes())
+            })))
+            .mount(server)
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_set_secret_seals_value() {
+        let server = MockServer::start().await;
+        let key = SecretKey::generate(&mut OsRng);
+        mount_public_key(&server, "ssh-signer", &key).await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/secrets/DEPLOY_TOKEN",
+            ))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let scope = SecretScope::Repo {
+            owner: "tarkalabs",
+            repo: "ssh-signer",
+        };
+        let write = gapi
+            .set_secret(scope, "DEPLOY_TOKEN", &secret("hunter2"))
+            .await
+            .unwrap();
+        assert_eq!(SecretWrite::Created, write);
+
+        let requests = server.received_requests().await.unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&requests[1].body).unwrap();
+        assert_eq!("568250167242549743", body["key_id"]);
+        assert!(body.get("visibility").is_none());
+        let sealed = base64::decode(body["encrypted_value"].as_str().unwrap()).unwrap();
+        assert_eq!(b"hunter2".to_vec(), key.unseal(&sealed).unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_set_secret_on_repos_reports_each_repo() {
+        let server = MockServer::start().await;
+        let key = SecretKey::generate(&mut OsRng);
+        mount_public_key(&server, "ssh-signer", &key).await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/secrets/DEPLOY_TOKEN",
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/archived/actions/secrets/public-key"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Not Found"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let outcomes = gapi
+            .set_secret_on_repos(
+                "tarkalabs",
+                &["ssh-signer".into(), "archived".into()],
+                "DEPLOY_TOKEN",
+                "hunter2",
+            )
+            .await;
+        assert_eq!("tarkalabs/ssh-signer", outcomes[0].repository);
+        assert_eq!(Some(SecretWrite::Updated), outcomes[0].result);
+        assert!(outcomes[1].result.is_none());
+        assert!(outcomes[1].error.is_some());
+    }
+}
@@ -0,0 +1,57 @@
+use super::{GHAPIError, GithubAPI};
+use bytes::Bytes;
+use error_stack::Result;
+use futures::{stream::BoxStream, StreamExt};
+use reqwest::header::{ACCEPT, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE};
+
+/// A response body that is passed on as it arrives instead of being
+/// buffered, along with the headers needed to forward it.
+pub struct ByteStream {
+    pub content_type: Option<String>,
+    pub content_length: Option<u64>,
+    /// Set when the body is only the requested range of the resource.
+    pub content_range: Option<String>,
+    pub body: BoxStream<'static, reqwest::Result<Bytes>>,
+}
+
+impl std::fmt::Debug for ByteStream {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ByteStream")
+            .field("content_type", &self.content_type)
+            .field("content_length", &self.content_length)
+            .field("content_range", &self.content_range)
+            .finish_non_exhaustive()
+    }
+}
+
+impl ByteStream {
+    pub(super) fn from_response(resp: reqwest::Response) -> ByteStream {
+        let header = |name| {
+            resp.headers()
+                .get(name)
+                .and_then(|value: &reqwest::header::HeaderValue| value.to_str().ok())
+                .map(String::from)
+        };
+        ByteStream {
+            content_type: header(CONTENT_TYPE),
+            content_length: header(CONTENT_LENGTH).and_then(|len| len.parse().ok()),
+            content_range: header(CONTENT_RANGE),
+            body: resp.bytes_stream().boxed(),
+        }
+    }
+}
+
+impl GithubAPI {
+    /// GETs `url` as `media_type` and streams the body. These responses
+    /// bypass the response cache.
+    pub(super) async fn get_stream(
+        &self,
+        url: String,
+        media_type: &'static str,
+    ) -> Result<ByteStream, GHAPIError> {
+        let resp = self
+            .send(self.client.get(url).header(ACCEPT, media_type))
+            .await?;
+        Ok(ByteStream::from_response(resp))
+    }
+}
@@ -0,0 +1,205 @@
+use super::{
+    commits::ObjectRef,
+    git::{GitObject, ObjectType},
+    models::{CommitIdentity, GitActor},
+    GHAPIError, GithubAPI,
+};
+use error_stack::{Report, Result};
+use reqwest::Method;
+use serde::{Deserialize, Serialize};
+
+/// Annotated tags may point at other tags; chains longer than this are
+/// treated as broken.
+const MAX_TAG_DEPTH: usize = 8;
+
+/// A tag as listed for a repository, lightweight or annotated.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Tag {
+    pub name: String,
+    /// The commit the tag finally points at.
+    pub commit: ObjectRef,
+    pub zipball_url: String,
+    pub tarball_url: String,
+    pub node_id: String,
+}
+
+/// An annotated tag object.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AnnotatedTag {
+    pub sha: String,
+    pub node_id: String,
+    /// The tag name.
+    pub tag: String,
+    pub message: String,
+    pub tagger: GitActor,
+    /// What the tag points at, usually a commit.
+    pub object: GitObject,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct NewTag {
+    pub tag: String,
+    pub message: String,
+    /// The SHA of the tagged object.
+    pub object: String,
+    #[serde(rename = "type", default = "default_object_type")]
+    pub object_type: ObjectType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tagger: Option<CommitIdentity>,
+}
+
+fn default_object_type() -> ObjectType {
+    ObjectType::Commit
+}
+
+/// Where a tag name leads once annotated tags are peeled.
+#[derive(Debug, Clone, Serialize)]
+pub struct ResolvedTag {
+    pub name: String,
+    /// The SHA of the tagged object, normally a commit.
+    pub sha: String,
+    pub object_type: ObjectType,
+    /// The SHA of the tag object, or `None` for a lightweight tag.
+    pub tag_sha: Option<String>,
+}
+
+impl GithubAPI {
+    pub async fn list_tags(
+        &self,
+        owner: &str,
+        repo: &str,
+        per_page: u8,
+        limit: usize,
+    ) -> Result<Vec<Tag>, GHAPIError> {
+        self.collect_paginated(&format!("repos/{}/{}/tags", owner, repo), per_page, limit)
+            .await
+    }
+
+    pub async fn get_tag_object(
+        &self,
+        owner: &str,
+        repo: &str,
+        sha: &str,
+    ) -> Result<AnnotatedTag, GHAPIError> {
+        self.get_json(&format!("repos/{}/{}/git/tags/{}", owner, repo, sha))
+            .await
+    }
+
+    /// Creates an annotated tag object and the `refs/tags/` ref pointing at
+    /// it. GitHub only lists the tag once the ref exists.
+    pub async fn create_annotated_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        tag: &NewTag,
+    ) -> Result<AnnotatedTag, GHAPIError> {
+        let path = format!("repos/{}/{}/git/tags", owner, repo);
+        let created: AnnotatedTag = self.send_json(Method::POST, &path, tag).await?;
+        self.create_ref(
+            owner,
+            repo,
+            &format!("refs/tags/{}", created.tag),
+            &created.sha,
+        )
+        .await?;
+        Ok(created)
+    }
+
+    /// Follows the tag `name` through any annotated tag objects to the
+    /// object it finally tags.
+    pub async fn resolve_tag(
+        &self,
+        owner: &str,
+        repo: &str,
+        name: &str,
+    ) -> Result<ResolvedTag, GHAPIError> {
+        let mut object = self
+            .get_ref(owner, repo, &format!("tags/{}", name))
+            .await?
+            .object;
+        let mut tag_sha = None;
+        for _ in 0..MAX_TAG_DEPTH {
+            if object.object_type != ObjectType::Tag {
+                return Ok(ResolvedTag {
+                    name: name.into(),
+                    sha: object.sha,
+                    object_type: object.object_type,
+                    tag_sha,
+                });
+            }
+            tag_sha.get_or_insert_with(|| object.sha.clone());
+            object = self.get_tag_object(owner, repo, &object.sha).await?.object;
+        }
+        Err(Report::new(GHAPIError::FailedToDeserialize)
+            .attach_printable(format!("tag {} is nested too deeply", name)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::{git::ObjectType, GithubAPI};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    static TAG_SHA: &str = "940bd336248efae0f9ee5bc7b2d5c985887b16ac";
+    static COMMIT_SHA: &str = "c3d0be41ecbe669545ee3e94d31ed9a4bc91ee3c";
+
+    fn tag_ref(name: &str, sha: &str, object_type: &str) -> ResponseTemplate {
+        ResponseTemplate::new(200).set_body_json(serde_json::json!({
+            "ref": format!("refs/tags/{}", name),
+            "node_id": "MDM6UmVmcmVmcy90YWdzL3YwLjIuMQ==",
+            "object": { "sha": sha, "type": object_type }
+        }))
+    }
+
+    #[tokio::test]
+    async fn test_resolve_annotated_and_lightweight_tags() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/ref/tags/v0.2.1"))
+            .respond_with(tag_ref("v0.2.1", TAG_SHA, "tag"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(format!(
+                "/repos/tarkalabs/ssh-signer/git/tags/{}",
+                TAG_SHA
+            )))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "sha": TAG_SHA,
+                "node_id": "TA_kwDOEaeHgdoAKDk0MGJkMzM2MjQ4ZWZhZTBmOWVlNWJjN2Iy",
+                "tag": "v0.2.1",
+                "message": "Release v0.2.1\n",
+                "tagger": {
+                    "name": "The Octocat",
+                    "email": "octocat@github.com",
+                    "date": "2022-08-01T09:10:00Z"
+                },
+                "object": { "sha": COMMIT_SHA, "type": "commit" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/ref/tags/nightly"))
+            .respond_with(tag_ref("nightly", COMMIT_SHA, "commit"))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let annotated = gapi
+            .resolve_tag("tarkalabs", "ssh-signer", "v0.2.1")
+            .await
+            .unwrap();
+        assert_eq!(COMMIT_SHA, annotated.sha);
+        assert_eq!(ObjectType::Commit, annotated.object_type);
+        assert_eq!(Some(TAG_SHA), annotated.tag_sha.as_deref());
+        let lightweight = gapi
+            .resolve_tag("tarkalabs", "ssh-signer", "nightly")
+            .await
+            .unwrap();
+        assert_eq!(COMMIT_SHA, lightweight.sha);
+        assert!(lightweight.tag_sha.is_none());
+    }
+}
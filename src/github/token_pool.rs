@@ -0,0 +1,298 @@
+use super::{cache::token_hash, rate_limit::now, GHAPIError, GithubAPI, RateLimit};
+use error_stack::{IntoReport, Report, Result, ResultExt};
+use reqwest::header::HeaderValue;
+use serde::Serialize;
+use std::cmp::Reverse;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// A set of static tokens that requests are spread across.
+pub(super) struct TokenPool {
+    tokens: Vec<PooledToken>,
+    next: AtomicUsize,
+    /// Held while a token is rejected, so that counting the healthy tokens
+    /// and taking one out of rotation happen as one step.
+    rejecting: Mutex<()>,
+}
+
+struct PooledToken {
+    header: HeaderValue,
+    hash: u64,
+    /// Cleared once GitHub rejects the token with a 401.
+    healthy: AtomicBool,
+}
+
+/// Attached to a 401 whose token just left the rotation, marking the
+/// request as worth retrying with another token.
+#[derive(Debug)]
+pub(super) struct TokenRotatedOut;
+
+/// The state of one token in the pool, identified by its position in the
+/// configuration rather than by any part of the secret.
+#[derive(Debug, Clone, Serialize)]
+pub struct TokenHealth {
+    pub index: usize,
+    pub healthy: bool,
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl TokenPool {
+    pub(super) fn new(tokens: Vec<String>) -> Result<TokenPool, GHAPIError> {
+        if tokens.is_empty() {
+            return Err(Report::new(GHAPIError::ClientCreationFailed)
+                .attach_printable("no tokens were given"));
+        }
+        let pooled = tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| {
+                let mut header = HeaderValue::from_str(&format!("token {}", token))
+                    .report()
+                    .change_context(GHAPIError::ClientCreationFailed)
+                    .attach_printable_lazy(|| {
+                        format!("token {} is not a valid header value", index)
+                    })?;
+                header.set_sensitive(true);
+                Ok(PooledToken {
+                    header,
+                    hash: token_hash(token),
+                    healthy: AtomicBool::new(true),
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(TokenPool {
+            tokens: pooled,
+            next: AtomicUsize::new(0),
+            rejecting: Mutex::default(),
+        })
+    }
+
+    /// Takes the token identified by `hash` out of rotation and returns
+    /// whether it did. The last healthy token always stays, so a single
+    /// spurious 401 cannot leave the pool without any token at all.
+    pub(super) fn reject(&self, hash: u64) -> bool {
+        let _rejecting = self.rejecting.lock().unwrap();
+        let healthy = self
+            .tokens
+            .iter()
+            .filter(|t| t.healthy.load(Ordering::Relaxed))
+            .count();
+        let index = match self
+            .tokens
+            .iter()
+            .position(|t| t.hash == hash && t.healthy.load(Ordering::Relaxed))
+        {
+            Some(index) => index,
+            None => return false,
+        };
+        if healthy <= 1 {
+            tracing::warn!(
+                "Token {} was rejected by GitHub, keeping it as the last healthy token",
+                index
+            );
+            return false;
+        }
+        self.tokens[index].healthy.store(false, Ordering::Relaxed);
+        tracing::warn!(
+            "Token {} was rejected by GitHub, removing it from rotation",
+            index
+        );
+        true
+    }
+}
+
+impl GithubAPI {
+    /// Picks the healthy token with the most remaining budget. Tokens with
+    /// equal budgets are used in turn, and when every budget is exhausted
+    /// the token that resets first is used.
+    pub(super) fn select_token(&self, pool: &TokenPool) -> Result<(HeaderValue, u64), GHAPIError> {
+        let now = now();
+        let start = pool.next.fetch_add(1, Ordering::Relaxed);
+        let len = pool.tokens.len();
+        let selected = (0..len)
+            .map(|offset| &pool.tokens[(start + offset) % len])
+            .filter(|token| token.healthy.load(Ordering::Relaxed))
+            .map(|token| {
+                let exhausted_until = self.exhausted_until(token.hash, now);
+                let remaining = self
+                    .rate_limit_for(token.hash)
+                    .map_or(u32::MAX, |rl| rl.remaining);
+                let score = (
+                    exhausted_until.is_none(),
+                    remaining,
+                    Reverse(exhausted_until.unwrap_or_default()),
+                );
+                (score, token)
+            })
+            // `max_by_key` keeps the last maximum, so reverse to keep the
+            // first in rotation order.
+            .rev()
+            .max_by_key(|(score, _)| *score);
+        match selected {
+            Some((_, token)) => Ok((token.header.clone(), token.hash)),
+            None => Err(Report::new(GHAPIError::AuthenticationFailed)
+                .attach_printable("every token in the pool was rejected")),
+        }
+    }
+
+    /// The health and latest rate limit of every pooled token. Empty when
+    /// authenticating as a GitHub App.
+    pub fn token_health(&self) -> Vec<TokenHealth> {
+        let pool = match &self.auth {
+            super::auth::Auth::Pool(pool) => pool,
+            super::auth::Auth::App(_) => return Vec::new(),
+        };
+        pool.tokens
+            .iter()
+            .enumerate()
+            .map(|(index, token)| TokenHealth {
+                index,
+                healthy: token.healthy.load(Ordering::Relaxed),
+                rate_limit: self.rate_limit_for(token.hash),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TokenPool;
+    use crate::github::{cache::token_hash, ClientOptions, Credentials, GithubAPI};
+    use std::sync::{Arc, Barrier};
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("fixtures/repository.json");
+
+    fn repo_response(remaining: u32) -> ResponseTemplate {
+        ResponseTemplate::new(200)
+            .set_body_string(MOCK_BODY)
+            .insert_header("Content-Type", "application/json")
+            .insert_header("X-RateLimit-Limit", "5000")
+            .insert_header("X-RateLimit-Remaining", remaining.to_string().as_str())
+            .insert_header("X-RateLimit-Reset", "4000000000")
+    }
+
+    fn pool_api(server: &MockServer, tokens: &[&str]) -> GithubAPI {
+        let tokens = tokens.iter().map(|t| t.to_string()).collect();
+        GithubAPI::with_options(
+            Credentials::Tokens(tokens),
+            Some(server.uri()),
+            ClientOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_least_depleted_token_is_preferred() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token first"))
+            .respond_with(repo_response(10))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token second"))
+            .respond_with(repo_response(4000))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let gapi = pool_api(&server, &["first", "second"]);
+        for _ in 0..4 {
+            gapi.get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_tokens_leave_rotation() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token revoked"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .set_body_json(serde_json::json!({ "message": "Bad credentials" })),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .and(header("Authorization", "token valid"))
+            .respond_with(repo_response(4000))
+            .expect(3)
+            .mount(&server)
+            .await;
+        let gapi = pool_api(&server, &["revoked", "valid"]);
+        for _ in 0..3 {
+            gapi.get_repository_details("tarkalabs/ssh-signer".into())
+                .await
+                .unwrap();
+        }
+        let health = gapi.token_health();
+        assert!(!health[0].healthy);
+        assert!(health[1].healthy);
+        assert_eq!(4000, health[1].rate_limit.as_ref().unwrap().remaining);
+    }
+
+    #[tokio::test]
+    async fn test_single_token_survives_a_rejection() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(401)
+                    .set_body_json(serde_json::json!({ "message": "Bad credentials" })),
+            )
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(repo_response(4000))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("only".into(), Some(server.uri())).unwrap();
+        assert!(gapi
+            .get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .is_err());
+        gapi.get_repository_details("tarkalabs/ssh-signer".into())
+            .await
+            .unwrap();
+        assert!(gapi.token_health()[0].healthy);
+    }
+
+    #[test]
+    fn test_concurrent_rejections_keep_one_token() {
+        let tokens: Vec<String> = (0..8).map(|i| format!("token-{}", i)).collect();
+        for _ in 0..200 {
+            let pool = Arc::new(TokenPool::new(tokens.clone()).unwrap());
+            let barrier = Arc::new(Barrier::new(tokens.len()));
+            let rejections: Vec<_> = tokens
+                .iter()
+                .map(|token| {
+                    let (pool, barrier, hash) = (pool.clone(), barrier.clone(), token_hash(token));
+                    std::thread::spawn(move || {
+                        barrier.wait();
+                        pool.reject(hash)
+                    })
+                })
+                .collect();
+            let rejected = rejections
+                .into_iter()
+                .map(|rejection| rejection.join().unwrap())
+                .filter(|&rejected| rejected)
+                .count();
+            assert_eq!(tokens.len() - 1, rejected);
+        }
+    }
+}
@@ -1,23 +1,73 @@
-use axum::{Router, routing::get, Server};
-use tower_http::trace::TraceLayer;
-use std::net::SocketAddr;
+use axum::Server;
+use cache::ServiceCache;
+use config::Config;
+use github::{GithubAPI, InMemoryLruCache};
+use std::sync::Arc;
+use std::time::Duration;
+use tracing_subscriber::EnvFilter;
 
+mod cache;
+mod config;
+mod error;
 mod github;
-
-async fn handler() -> String {
-    "hello world".into()
-}
+mod routes;
 
 #[tokio::main]
 async fn main() {
-    tracing_subscriber::fmt::init();
+    let config = match Config::from_env() {
+        Ok(config) => config,
+        Err(report) => {
+            eprintln!("Failed to load configuration: {:?}", report);
+            std::process::exit(1);
+        }
+    };
+    let filter =
+        EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(&config.log.filter));
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    tracing::debug!("Loaded configuration {:?}", config);
 
-    let router = Router::new()
-    .route("/", get(handler))
-    .layer(TraceLayer::new_for_http());
+    let credentials = config.credentials().and_then(|credentials| {
+        config
+            .client_options()
+            .map(|options| (credentials, options))
+    });
+    let (credentials, options) = match credentials {
+        Ok(loaded) => loaded,
+        Err(report) => {
+            tracing::error!("Failed to load credentials or TLS settings: {:?}", report);
+            std::process::exit(1);
+        }
+    };
+    let gapi = match GithubAPI::with_options(credentials, Some(config.api_url()), options) {
+        Ok(gapi) => gapi,
+        Err(report) => {
+            tracing::error!("Failed to create the GitHub client: {:?}", report);
+            std::process::exit(1);
+        }
+    };
+    let mut gapi = gapi
+        .with_max_rate_limit_wait(Duration::from_secs(config.github.max_rate_limit_wait_secs))
+        .with_retry_policy(config.retry_policy());
+    if config.cache.conditional_capacity > 0 {
+        let lru = InMemoryLruCache::new(config.cache.conditional_capacity);
+        gapi = gapi.with_response_cache(Arc::new(lru));
+    }
+    tracing::debug!(
+        "Using GitHub API at {} and uploads at {}",
+        gapi.base_url(),
+        gapi.upload_url()
+    );
+    let cache = ServiceCache::new(
+        Duration::from_secs(config.cache.ttl_secs),
+        Duration::from_secs(config.cache.stale_secs),
+    );
+    let router = routes::app(gapi, cache);
 
-    let addr: SocketAddr = ([127,0,0,1], 3000).into();
+    let addr = config.server.bind;
 
     tracing::debug!("Listening on port {:?}", addr);
-    Server::bind(&addr).serve(router.into_make_service()).await.unwrap()
+    Server::bind(&addr)
+        .serve(router.into_make_service())
+        .await
+        .unwrap()
 }
@@ -0,0 +1,513 @@
+use super::{stream_response, ListParams};
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{GithubAPI, RunFilter, WorkflowDispatch};
+use axum::{
+    extract::{Path, Query},
+    http::{
+        header::{ACCEPT_RANGES, CONTENT_TYPE, RANGE},
+        HeaderMap, HeaderValue, StatusCode, Uri,
+    },
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use regex::Regex;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Runs change state as soon as they are dispatched, rerun or cancelled.
+fn purge_runs(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/actions", owner, repo)));
+}
+
+async fn list_workflows(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_workflows(&owner, &repo, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn list_runs(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(filter): Query<RunFilter>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_workflow_runs(
+                    &owner,
+                    &repo,
+                    None,
+                    &filter,
+                    params.per_page(),
+                    params.limit(),
+                )
+                .await?)
+        })
+        .await
+}
+
+async fn list_workflow_runs(
+    uri: Uri,
+    Path((owner, repo, workflow)): Path<(String, String, String)>,
+    Query(filter): Query<RunFilter>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_workflow_runs(
+                    &owner,
+                    &repo,
+                    Some(&workflow),
+                    &filter,
+                    params.per_page(),
+                    params.limit(),
+                )
+                .await?)
+        })
+        .await
+}
+
+async fn get_run(
+    uri: Uri,
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_workflow_run(&owner, &repo, run_id).await?)
+        })
+        .await
+}
+
+async fn list_jobs(
+    uri: Uri,
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_run_jobs(&owner, &repo, run_id, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn dispatch_workflow(
+    Path((owner, repo, workflow)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(dispatch): Json<WorkflowDispatch>,
+) -> Result<StatusCode, AppError> {
+    gapi.dispatch_workflow(&owner, &repo, &workflow, &dispatch)
+        .await?;
+    purge_runs(&cache, &owner, &repo);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct RerunParams {
+    #[serde(default)]
+    failed_only: bool,
+}
+
+async fn rerun(
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Query(params): Query<RerunParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.rerun_workflow_run(&owner, &repo, run_id, params.failed_only)
+        .await?;
+    purge_runs(&cache, &owner, &repo);
+    Ok(StatusCode::CREATED)
+}
+
+async fn cancel(
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.cancel_workflow_run(&owner, &repo, run_id).await?;
+    purge_runs(&cache, &owner, &repo);
+    Ok(StatusCode::ACCEPTED)
+}
+
+#[derive(Debug, Deserialize)]
+struct ArtifactParams {
+    name: Option<String>,
+}
+
+async fn list_artifacts(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(filter): Query<ArtifactParams>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_artifacts(
+                    &owner,
+                    &repo,
+                    filter.name.as_deref(),
+                    params.per_page(),
+                    params.limit(),
+                )
+                .await?)
+        })
+        .await
+}
+
+async fn list_run_artifacts(
+    uri: Uri,
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_run_artifacts(&owner, &repo, run_id, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn get_artifact(
+    uri: Uri,
+    Path((owner, repo, artifact_id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_artifact(&owner, &repo, artifact_id).await?)
+        })
+        .await
+}
+
+/// Proxies an artifact's zip archive, passing a `Range` request on so that
+/// interrupted downloads can resume.
+async fn download_artifact(
+    Path((owner, repo, artifact_id)): Path<(String, String, u64)>,
+    headers: HeaderMap,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<Response, AppError> {
+    let range = headers.get(RANGE).and_then(|value| value.to_str().ok());
+    let stream = gapi
+        .download_artifact(&owner, &repo, artifact_id, range)
+        .await?;
+    let mut resp = stream_response("application/zip", stream);
+    resp.headers_mut()
+        .insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+    Ok(resp)
+}
+
+/// Narrows a log to the 1-based, inclusive line range `from..=to` and then
+/// to the lines matching `pattern`.
+#[derive(Debug, Deserialize)]
+struct LogFilter {
+    from: Option<usize>,
+    to: Option<usize>,
+    pattern: Option<String>,
+}
+
+impl LogFilter {
+    fn regex(&self) -> Result<Option<Regex>, AppError> {
+        self.pattern
+            .as_deref()
+            .map(Regex::new)
+            .transpose()
+            .map_err(|err| AppError::BadRequest(format!("invalid pattern: {}", err)))
+    }
+
+    fn apply(&self, log: &str, regex: Option<&Regex>) -> String {
+        let from = self.from.unwrap_or(1).max(1);
+        let to = self.to.unwrap_or(usize::MAX);
+        log.lines()
+            .enumerate()
+            .filter(|(i, _)| (from..=to).contains(&(i + 1)))
+            .map(|(_, line)| line)
+            .filter(|line| match regex {
+                Some(regex) => regex.is_match(line),
+                None => true,
+            })
+            .fold(String::new(), |mut out, line| {
+                out.push_str(line);
+                out.push('\n');
+                out
+            })
+    }
+}
+
+/// Logs are large and only final once a job completes, so they bypass the
+/// cache.
+async fn job_logs(
+    Path((owner, repo, job_id)): Path<(String, String, u64)>,
+    Query(filter): Query<LogFilter>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<impl IntoResponse, AppError> {
+    let regex = filter.regex()?;
+    let log = gapi.get_job_logs(&owner, &repo, job_id).await?;
+    Ok((
+        [(CONTENT_TYPE, "text/plain; charset=utf-8")],
+        filter.apply(&log, regex.as_ref()),
+    ))
+}
+
+/// Every log file of a run, each filtered on its own and headed by its name.
+/// Files left empty by the filter are dropped.
+async fn run_logs(
+    Path((owner, repo, run_id)): Path<(String, String, u64)>,
+    Query(filter): Query<LogFilter>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<impl IntoResponse, AppError> {
+    let regex = filter.regex()?;
+    let mut out = String::new();
+    for file in gapi.get_run_logs(&owner, &repo, run_id).await? {
+        let lines = filter.apply(&file.content, regex.as_ref());
+        if !lines.is_empty() {
+            out.push_str(&format!("==> {} <==\n{}", file.name, lines));
+        }
+    }
+    Ok(([(CONTENT_TYPE, "text/plain; charset=utf-8")], out))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route("/repos/:owner/:repo/actions/workflows", get(list_workflows))
+        .route(
+            "/repos/:owner/:repo/actions/workflows/:workflow/runs",
+            get(list_workflow_runs),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/workflows/:workflow/dispatches",
+            post(dispatch_workflow),
+        )
+        .route("/repos/:owner/:repo/actions/artifacts", get(list_artifacts))
+        .route(
+            "/repos/:owner/:repo/actions/artifacts/:artifact_id",
+            get(get_artifact),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/artifacts/:artifact_id/zip",
+            get(download_artifact),
+        )
+        .route("/repos/:owner/:repo/actions/runs", get(list_runs))
+        .route("/repos/:owner/:repo/actions/runs/:run_id", get(get_run))
+        .route(
+            "/repos/:owner/:repo/actions/runs/:run_id/jobs",
+            get(list_jobs),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/runs/:run_id/artifacts",
+            get(list_run_artifacts),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/runs/:run_id/logs",
+            get(run_logs),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/jobs/:job_id/logs",
+            get(job_logs),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/runs/:run_id/rerun",
+            post(rerun),
+        )
+        .route(
+            "/repos/:owner/:repo/actions/runs/:run_id/cancel",
+            post(cancel),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static RUN: &str = include_str!("../github/fixtures/workflow_run.json");
+
+    #[tokio::test]
+    async fn test_list_runs_route_passes_filters() {
+        let server = MockServer::start().await;
+        let run: serde_json::Value = serde_json::from_str(RUN).unwrap();
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/actions/runs"))
+            .and(query_param("branch", "main"))
+            .and(query_param("event", "push"))
+            .and(query_param("per_page", "5"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "total_count": 1,
+                "workflow_runs": [run]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/actions/runs?branch=main&event=push&per_page=5",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("failure", body[0]["conclusion"]);
+    }
+
+    #[tokio::test]
+    async fn test_job_logs_route_filters_lines() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/jobs/7713419312/logs",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_string(
+                "##[group]Run cargo test\n\
+                 test verify ... ok\n\
+                 test sign ... FAILED\n\
+                 test parse ... FAILED\n\
+                 ##[error]Process completed with exit code 101.\n",
+            ))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let url = format!(
+            "http://{}/repos/tarkalabs/ssh-signer/actions/jobs/7713419312/logs",
+            addr
+        );
+        let resp = reqwest::get(format!("{}?from=2&to=3&pattern=FAILED", url))
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!("test sign ... FAILED\n", resp.text().await.unwrap());
+        let resp = reqwest::get(format!("{}?pattern=(unclosed", url))
+            .await
+            .unwrap();
+        assert_eq!(400, resp.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_artifact_download_route_passes_range() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+            ))
+            .and(header("Range", "bytes=2-3"))
+            .respond_with(
+                ResponseTemplate::new(206)
+                    .insert_header("Content-Range", "bytes 2-3/48213")
+                    .set_body_bytes(b"\x03\x04".to_vec()),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .get(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+                addr
+            ))
+            .header("Range", "bytes=2-3")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(206, resp.status().as_u16());
+        assert_eq!("bytes 2-3/48213", resp.headers()["Content-Range"]);
+        assert_eq!(Some(2), resp.content_length());
+        assert_eq!(b"\x03\x04".to_vec(), resp.bytes().await.unwrap().to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_artifact_download_route_passes_unsatisfiable_range() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+            ))
+            .and(header("Range", "bytes=50000-"))
+            .respond_with(
+                ResponseTemplate::new(416).insert_header("Content-Range", "bytes */48213"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .get(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+                addr
+            ))
+            .header("Range", "bytes=50000-")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(416, resp.status().as_u16());
+        assert_eq!("bytes */48213", resp.headers()["Content-Range"]);
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("range_not_satisfiable", body["error"]["code"]);
+    }
+
+    #[tokio::test]
+    async fn test_artifact_download_route_passes_expired() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+            ))
+            .respond_with(ResponseTemplate::new(410).set_body_json(serde_json::json!({
+                "message": "Artifact has expired",
+                "documentation_url": "https://docs.github.com/rest/actions/artifacts#download-an-artifact"
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/actions/artifacts/330147421/zip",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert_eq!(410, resp.status().as_u16());
+        assert!(resp.headers().get("Content-Range").is_none());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("gone", body["error"]["code"]);
+        assert_eq!("Artifact has expired", body["error"]["message"]);
+    }
+}
@@ -0,0 +1,315 @@
+use super::ListParams;
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{
+    BranchProtection, GithubAPI, ProtectionPolicy, ProtectionUpdate, Reference, Repository,
+};
+use axum::{
+    extract::{Path, Query},
+    http::{StatusCode, Uri},
+    routing::get,
+    Extension, Json, Router,
+};
+use futures::{stream, StreamExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// How many repositories the protection report checks at once.
+const REPORT_CONCURRENCY: usize = 8;
+
+fn purge_branches(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/branches", owner, repo)));
+}
+
+async fn list_branches(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_branches(&owner, &repo, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+struct NewBranch {
+    name: String,
+    sha: String,
+}
+
+async fn create_branch(
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(branch): Json<NewBranch>,
+) -> Result<(StatusCode, Json<Reference>), AppError> {
+    let reference = gapi
+        .create_branch(&owner, &repo, &branch.name, &branch.sha)
+        .await?;
+    purge_branches(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(reference)))
+}
+
+async fn get_branch(
+    uri: Uri,
+    Path((owner, repo, branch)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_branch(&owner, &repo, &branch).await?)
+        })
+        .await
+}
+
+async fn delete_branch(
+    Path((owner, repo, branch)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.delete_branch(&owner, &repo, &branch).await?;
+    purge_branches(&cache, &owner, &repo);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The branch's protection rules, or `null` when it is not protected.
+async fn get_protection(
+    uri: Uri,
+    Path((owner, repo, branch)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_branch_protection(&owner, &repo, &branch).await?)
+        })
+        .await
+}
+
+async fn update_protection(
+    Path((owner, repo, branch)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(update): Json<ProtectionUpdate>,
+) -> Result<Json<BranchProtection>, AppError> {
+    let protection = gapi
+        .update_branch_protection(&owner, &repo, &branch, &update)
+        .await?;
+    purge_branches(&cache, &owner, &repo);
+    Ok(Json(protection))
+}
+
+#[derive(Debug, Deserialize)]
+struct ReportParams {
+    min_approvals: Option<u8>,
+    /// Comma separated status check names.
+    checks: Option<String>,
+    enforce_admins: Option<bool>,
+}
+
+impl ReportParams {
+    fn policy(&self) -> ProtectionPolicy {
+        ProtectionPolicy {
+            min_approvals: self.min_approvals.unwrap_or(1),
+            status_checks: self
+                .checks
+                .iter()
+                .flat_map(|checks| checks.split(','))
+                .map(str::trim)
+                .filter(|check| !check.is_empty())
+                .map(String::from)
+                .collect(),
+            enforce_admins: self.enforce_admins.unwrap_or(false),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct Finding {
+    repository: String,
+    default_branch: String,
+    missing: Vec<String>,
+    /// Set when the protection could not be read, e.g. for lack of
+    /// permission.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct ProtectionReport {
+    policy: ProtectionPolicy,
+    checked: usize,
+    findings: Vec<Finding>,
+}
+
+/// Every unarchived repository of `org` whose default branch falls short of
+/// the protection policy given in the query.
+async fn protection_report(
+    uri: Uri,
+    Path(org): Path<String>,
+    Query(report): Query<ReportParams>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    let policy = report.policy();
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            // Every repository is checked; `limit` only caps the findings
+            // returned.
+            let repos: Vec<Repository> = gapi
+                .paginate(&format!("orgs/{}/repos", org), params.per_page())
+                .try_collect()
+                .await?;
+            let repos: Vec<_> = repos.into_iter().filter(|repo| !repo.archived).collect();
+            let checked = repos.len();
+            let mut findings: Vec<Finding> = stream::iter(repos)
+                .map(|repo| {
+                    let (gapi, policy) = (&gapi, &policy);
+                    async move {
+                        let protection = gapi
+                            .get_branch_protection(
+                                &repo.owner.login,
+                                &repo.name,
+                                &repo.default_branch,
+                            )
+                            .await;
+                        let (missing, error) = match protection {
+                            Ok(protection) => (policy.violations(protection.as_ref()), None),
+                            Err(report) => (Vec::new(), Some(report.current_context().to_string())),
+                        };
+                        Finding {
+                            repository: repo.full_name,
+                            default_branch: repo.default_branch,
+                            missing,
+                            error,
+                        }
+                    }
+                })
+                .buffered(REPORT_CONCURRENCY)
+                .filter(|finding| {
+                    let keep = !finding.missing.is_empty() || finding.error.is_some();
+                    async move { keep }
+                })
+                .collect()
+                .await;
+            findings.truncate(params.limit());
+            Ok(ProtectionReport {
+                policy,
+                checked,
+                findings,
+            })
+        })
+        .await
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route(
+            "/repos/:owner/:repo/branches",
+            get(list_branches).post(create_branch),
+        )
+        .route(
+            "/repos/:owner/:repo/branches/:branch",
+            get(get_branch).delete(delete_branch),
+        )
+        .route(
+            "/repos/:owner/:repo/branches/:branch/protection",
+            get(get_protection).put(update_protection),
+        )
+        .route("/orgs/:org/protection-report", get(protection_report))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{method, path, path_regex},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static REPO: &str = include_str!("../github/fixtures/repository.json");
+    static PROTECTION: &str = include_str!("../github/fixtures/branch_protection.json");
+
+    #[tokio::test]
+    async fn test_protection_report() {
+        let server = MockServer::start().await;
+        let repo: serde_json::Value = serde_json::from_str(REPO).unwrap();
+        // More unprotected repositories than are checked at once, so that
+        // a `limit` reached early would leave some of them unchecked.
+        let mut repos: Vec<_> = (0..10)
+            .map(|i| {
+                let mut unprotected = repo.clone();
+                unprotected["name"] = format!("scratch-{}", i).into();
+                unprotected["full_name"] = format!("tarkalabs/scratch-{}", i).into();
+                unprotected
+            })
+            .collect();
+        let mut archived = repo.clone();
+        archived["name"] = "old".into();
+        archived["archived"] = true.into();
+        repos.extend([repo, archived]);
+        Mock::given(method("GET"))
+            .and(path("/orgs/tarkalabs/repos"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(&repos))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/branches/main/protection"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(PROTECTION)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path_regex(
+                "^/repos/tarkalabs/scratch-\\d+/branches/main/protection$",
+            ))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Branch not protected"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/orgs/tarkalabs/protection-report?checks=ci/build",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(11, body["checked"]);
+        assert_eq!(10, body["findings"].as_array().unwrap().len());
+        assert_eq!("tarkalabs/scratch-0", body["findings"][0]["repository"]);
+        assert_eq!(2, body["findings"][0]["missing"].as_array().unwrap().len());
+        // `limit` caps the findings, never the repositories checked.
+        let body: serde_json::Value = reqwest::get(format!(
+            "http://{}/orgs/tarkalabs/protection-report?checks=ci/build&limit=1",
+            addr
+        ))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+        assert_eq!(11, body["checked"]);
+        assert_eq!(1, body["findings"].as_array().unwrap().len());
+        let requests = server.received_requests().await.unwrap();
+        let checks = requests
+            .iter()
+            .filter(|r| r.url.path().ends_with("/protection"))
+            .count();
+        assert_eq!(22, checks);
+    }
+}
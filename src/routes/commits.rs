@@ -0,0 +1,171 @@
+use super::ListParams;
+use crate::cache::ServiceCache;
+use crate::error::AppError;
+use crate::github::{CommitFilter, DiffFormat, GithubAPI};
+use axum::{
+    extract::{Path, Query},
+    http::{header::CONTENT_TYPE, Uri},
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Router,
+};
+use std::sync::Arc;
+
+/// Splits a trailing `.diff` or `.patch` off a ref, as github.com URLs do.
+fn split_format(reference: &str) -> (&str, Option<DiffFormat>) {
+    if let Some(reference) = reference.strip_suffix(".diff") {
+        (reference, Some(DiffFormat::Diff))
+    } else if let Some(reference) = reference.strip_suffix(".patch") {
+        (reference, Some(DiffFormat::Patch))
+    } else {
+        (reference, None)
+    }
+}
+
+fn raw_response(text: String) -> Response {
+    ([(CONTENT_TYPE, "text/plain; charset=utf-8")], text).into_response()
+}
+
+async fn list_commits(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(filter): Query<CommitFilter>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<Response, AppError> {
+    let commits = cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_commits(&owner, &repo, &filter, params.per_page(), params.limit())
+                .await?)
+        })
+        .await?;
+    Ok(commits.into_response())
+}
+
+/// A single commit as JSON, or as a diff or patch when the ref ends in
+/// `.diff` or `.patch`.
+async fn get_commit(
+    uri: Uri,
+    Path((owner, repo, reference)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<Response, AppError> {
+    let reference = reference.trim_start_matches('/').to_string();
+    if let (reference, Some(format)) = split_format(&reference) {
+        let diff = gapi
+            .get_commit_diff(&owner, &repo, reference, format)
+            .await?;
+        return Ok(raw_response(diff));
+    }
+    let commit = cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_commit(&owner, &repo, &reference).await?)
+        })
+        .await?;
+    Ok(commit.into_response())
+}
+
+/// Compares `base...head`, as JSON or, with a `.diff` or `.patch` suffix,
+/// as raw text.
+async fn compare(
+    uri: Uri,
+    Path((owner, repo, basehead)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<Response, AppError> {
+    let (basehead, format) = split_format(basehead.trim_start_matches('/'));
+    let (base, head) = basehead
+        .split_once("...")
+        .ok_or_else(|| AppError::BadRequest("expected {base}...{head}".into()))?;
+    let (base, head) = (base.to_string(), head.to_string());
+    if let Some(format) = format {
+        let diff = gapi
+            .compare_diff(&owner, &repo, &base, &head, format)
+            .await?;
+        return Ok(raw_response(diff));
+    }
+    let comparison = cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.compare(&owner, &repo, &base, &head).await?)
+        })
+        .await?;
+    Ok(comparison.into_response())
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route("/repos/:owner/:repo/commits", get(list_commits))
+        .route("/repos/:owner/:repo/commits/*reference", get(get_commit))
+        .route("/repos/:owner/:repo/compare/*basehead", get(compare))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{header, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_commit_patch_route() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/commits/6dcb09b"))
+            .and(header("Accept", "application/vnd.github.patch"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("From 6dcb09b Mon Sep 17"))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/commits/6dcb09b.patch",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert_eq!("text/plain; charset=utf-8", resp.headers()["content-type"]);
+        assert_eq!("From 6dcb09b Mon Sep 17", resp.text().await.unwrap());
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/compare/main..fix",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert_eq!(400, resp.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_slashed_refs_and_non_utf8_diffs() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/commits/feature/x"))
+            .and(header("Accept", "application/vnd.github.patch"))
+            .respond_with(ResponseTemplate::new(200).set_body_string("From 6dcb09b Mon Sep 17"))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/compare/release/1.0...main",
+            ))
+            .and(header("Accept", "application/vnd.github.diff"))
+            .respond_with(ResponseTemplate::new(200).set_body_bytes(b"+caf\xe9\n".to_vec()))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let base = format!("http://{}/repos/tarkalabs/ssh-signer", addr);
+        let resp = reqwest::get(format!("{}/commits/feature/x.patch", base))
+            .await
+            .unwrap();
+        assert_eq!("From 6dcb09b Mon Sep 17", resp.text().await.unwrap());
+        let resp = reqwest::get(format!("{}/compare/release/1.0...main.diff", base))
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!("+caf\u{fffd}\n", resp.text().await.unwrap());
+    }
+}
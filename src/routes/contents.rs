@@ -0,0 +1,157 @@
+use super::stream_response;
+use crate::cache::ServiceCache;
+use crate::error::AppError;
+use crate::github::{ByteStream, FileCommit, FileDeletion, FileUpdate, GithubAPI};
+use axum::{
+    extract::{Path, Query},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::get,
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+#[derive(Debug, Deserialize)]
+struct RefParams {
+    #[serde(rename = "ref")]
+    reference: Option<String>,
+}
+
+/// Streams a raw file. GitHub labels every raw file as text/plain or
+/// octet-stream, so the type is guessed from the file name instead.
+fn raw_file(path: &str, stream: ByteStream) -> Response {
+    let content_type = mime_guess::from_path(path).first_or_octet_stream();
+    stream_response(content_type.as_ref(), stream)
+}
+
+/// A file's raw bytes, streamed as they arrive. Directories, symlinks and
+/// submodules are described as JSON instead.
+async fn get_contents(
+    Path((owner, repo, path)): Path<(String, String, String)>,
+    Query(params): Query<RefParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<Response, AppError> {
+    let path = path.trim_start_matches('/');
+    let reference = params.reference.as_deref();
+    let stream = gapi.stream_contents(&owner, &repo, path, reference).await?;
+    let is_json = matches!(
+        stream.content_type.as_deref(),
+        Some(ty) if ty.starts_with("application/json")
+    );
+    if !is_json {
+        return Ok(raw_file(path, stream));
+    }
+    Ok(Json(gapi.get_contents(&owner, &repo, path, reference).await?).into_response())
+}
+
+/// A file write is a new commit, so cached commit listings go stale.
+fn purge_commits(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/commits", owner, repo)));
+}
+
+async fn put_file(
+    Path((owner, repo, path)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(update): Json<FileUpdate>,
+) -> Result<(StatusCode, Json<FileCommit>), AppError> {
+    let created = update.sha.is_none();
+    let written = gapi.put_file(&owner, &repo, &path, &update).await?;
+    purge_commits(&cache, &owner, &repo);
+    let status = if created {
+        StatusCode::CREATED
+    } else {
+        StatusCode::OK
+    };
+    Ok((status, Json(written)))
+}
+
+async fn delete_file(
+    Path((owner, repo, path)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(deletion): Json<FileDeletion>,
+) -> Result<Json<FileCommit>, AppError> {
+    let deleted = gapi.delete_file(&owner, &repo, &path, &deletion).await?;
+    purge_commits(&cache, &owner, &repo);
+    Ok(Json(deleted))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new().route(
+        "/repos/:owner/:repo/contents/*path",
+        get(get_contents).put(put_file).delete(delete_file),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_contents_route_streams_raw_bytes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/docs/logo.png"))
+            .and(query_param("ref", "main"))
+            .and(header("Accept", "application/vnd.github.raw"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_bytes(vec![0x89, b'P', b'N', b'G'])
+                    .insert_header("Content-Type", "application/octet-stream"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/contents/docs/logo.png?ref=main",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        assert_eq!("image/png", resp.headers()["content-type"]);
+        assert_eq!("4", resp.headers()["content-length"]);
+        assert_eq!(
+            vec![0x89, b'P', b'N', b'G'],
+            resp.bytes().await.unwrap().to_vec()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_stale_sha_is_a_conflict() {
+        let server = MockServer::start().await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/contents/config/app.toml"))
+            .respond_with(ResponseTemplate::new(409).set_body_json(serde_json::json!({
+                "message": "config/app.toml does not match 3d21ec5"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .put(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/contents/config/app.toml",
+                addr
+            ))
+            .json(&serde_json::json!({
+                "message": "Bump signer port",
+                "content": "W3NlcnZlcl0KcG9ydCA9IDMwMDEK",
+                "sha": "3d21ec5"
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(409, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("conflict", body["error"]["code"]);
+    }
+}
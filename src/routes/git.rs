@@ -0,0 +1,59 @@
+use crate::cache::ServiceCache;
+use crate::error::AppError;
+use crate::github::{FileChanges, GitCommit, GithubAPI};
+use axum::{extract::Path, http::StatusCode, routing::post, Extension, Json, Router};
+use std::sync::Arc;
+
+/// Commits several file changes to a branch at once.
+async fn commit_files(
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(changes): Json<FileChanges>,
+) -> Result<(StatusCode, Json<GitCommit>), AppError> {
+    let commit = gapi.commit_files(&owner, &repo, &changes).await?;
+    cache.purge(None, Some(&format!("/repos/{}/{}/commits", owner, repo)));
+    Ok((StatusCode::CREATED, Json(commit)))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new().route("/repos/:owner/:repo/git/changes", post(commit_files))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_commit_to_missing_branch() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/ref/heads/nope"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Not Found"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .post(format!("http://{}/repos/tarkalabs/ssh-signer/git/changes", addr))
+            .json(&serde_json::json!({
+                "branch": "nope",
+                "message": "Regenerate clients",
+                "changes": [
+                    { "action": "write", "path": "src/client.rs", "content": "cHViIGZuIHNpZ24oKSB7fQo=" },
+                    { "action": "delete", "path": "src/legacy.rs" }
+                ]
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(404, resp.status().as_u16());
+    }
+}
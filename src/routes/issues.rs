@@ -0,0 +1,184 @@
+use super::ListParams;
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{
+    GithubAPI, Issue, IssueComment, IssueFilter, IssueUpdate, NewComment, NewIssue,
+};
+use axum::{
+    extract::{Path, Query},
+    http::{StatusCode, Uri},
+    routing::get,
+    Extension, Json, Router,
+};
+use std::sync::Arc;
+
+/// Drops every cached issue listing and issue of the repository after a
+/// write, so readers see the change right away.
+fn purge_issues(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/issues", owner, repo)));
+}
+
+async fn list_issues(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(filter): Query<IssueFilter>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_issues(&owner, &repo, &filter, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_issue(
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(issue): Json<NewIssue>,
+) -> Result<(StatusCode, Json<Issue>), AppError> {
+    let issue = gapi.create_issue(&owner, &repo, &issue).await?;
+    purge_issues(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(issue)))
+}
+
+async fn get_issue(
+    uri: Uri,
+    Path((owner, repo, number)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_issue(&owner, &repo, number).await?)
+        })
+        .await
+}
+
+async fn update_issue(
+    Path((owner, repo, number)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(update): Json<IssueUpdate>,
+) -> Result<Json<Issue>, AppError> {
+    let issue = gapi.update_issue(&owner, &repo, number, &update).await?;
+    purge_issues(&cache, &owner, &repo);
+    Ok(Json(issue))
+}
+
+async fn list_comments(
+    uri: Uri,
+    Path((owner, repo, number)): Path<(String, String, u64)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_issue_comments(&owner, &repo, number, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_comment(
+    Path((owner, repo, number)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(comment): Json<NewComment>,
+) -> Result<(StatusCode, Json<IssueComment>), AppError> {
+    let comment = gapi
+        .create_issue_comment(&owner, &repo, number, &comment)
+        .await?;
+    purge_issues(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route(
+            "/repos/:owner/:repo/issues",
+            get(list_issues).post(create_issue),
+        )
+        .route(
+            "/repos/:owner/:repo/issues/:number",
+            get(get_issue).patch(update_issue),
+        )
+        .route(
+            "/repos/:owner/:repo/issues/:number/comments",
+            get(list_comments).post(create_comment),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{body_json, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static ISSUE: &str = include_str!("../github/fixtures/issue.json");
+    static COMMENT: &str = include_str!("../github/fixtures/issue_comment.json");
+
+    fn json_response(status: u16, body: String) -> ResponseTemplate {
+        ResponseTemplate::new(status)
+            .set_body_string(body)
+            .insert_header("Content-Type", "application/json")
+    }
+
+    #[tokio::test]
+    async fn test_issue_routes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/issues"))
+            .and(query_param("assignee", "octocat"))
+            .respond_with(json_response(200, format!("[{}]", ISSUE)))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/issues/17/comments"))
+            .and(body_json(
+                serde_json::json!({ "body": "Reproduced with OpenSSH 9.0." }),
+            ))
+            .respond_with(json_response(201, COMMENT.to_string()))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let client = reqwest::Client::new();
+        let list_url = format!(
+            "http://{}/repos/tarkalabs/ssh-signer/issues?assignee=octocat&limit=5",
+            addr
+        );
+        let issues: serde_json::Value = client
+            .get(&list_url)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(17, issues[0]["number"]);
+        let resp = client
+            .post(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/issues/17/comments",
+                addr
+            ))
+            .json(&serde_json::json!({ "body": "Reproduced with OpenSSH 9.0." }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(201, resp.status().as_u16());
+        // The comment purged the cached listing.
+        let resp = client.get(&list_url).send().await.unwrap();
+        assert_eq!("MISS", resp.headers()["x-cache"]);
+    }
+}
@@ -0,0 +1,308 @@
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{ByteStream, GithubAPI, RateLimit, TokenHealth, DEFAULT_PER_PAGE};
+use axum::{
+    body::StreamBody,
+    extract::{Path, Query},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE},
+        StatusCode, Uri,
+    },
+    response::{IntoResponse, Response},
+    routing::{delete, get},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tower_http::trace::TraceLayer;
+
+mod actions;
+mod branches;
+mod commits;
+mod contents;
+mod git;
+mod issues;
+mod pulls;
+mod releases;
+mod secrets;
+mod tags;
+
+async fn handler() -> String {
+    "hello world".into()
+}
+
+async fn repository_details(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .get_repository_details(format!("{}/{}", owner, repo))
+                .await?)
+        })
+        .await
+}
+
+#[derive(Debug, Deserialize)]
+struct ListParams {
+    per_page: Option<u8>,
+    limit: Option<usize>,
+}
+
+impl ListParams {
+    const DEFAULT_LIMIT: usize = 100;
+
+    fn per_page(&self) -> u8 {
+        self.per_page.unwrap_or(DEFAULT_PER_PAGE)
+    }
+
+    fn limit(&self) -> usize {
+        self.limit.unwrap_or(Self::DEFAULT_LIMIT)
+    }
+}
+
+/// Passes an upstream body on as it arrives, keeping its length when known.
+/// A partial upstream body is passed on as a 206 with its range.
+fn stream_response(content_type: &str, stream: ByteStream) -> Response {
+    let mut resp = (
+        [(CONTENT_TYPE, content_type.to_string())],
+        StreamBody::new(stream.body),
+    )
+        .into_response();
+    if let Some(len) = stream.content_length {
+        resp.headers_mut().insert(CONTENT_LENGTH, len.into());
+    }
+    if let Some(range) = stream.content_range.and_then(|range| range.parse().ok()) {
+        *resp.status_mut() = StatusCode::PARTIAL_CONTENT;
+        resp.headers_mut().insert(CONTENT_RANGE, range);
+    }
+    resp
+}
+
+async fn org_repositories(
+    uri: Uri,
+    Path(org): Path<String>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_org_repositories(&org, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn rate_limit(
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<Json<Option<RateLimit>>, AppError> {
+    let rate_limit = match gapi.rate_limit() {
+        Some(rate_limit) => Some(rate_limit),
+        None => gapi.refresh_rate_limit().await?,
+    };
+    Ok(Json(rate_limit))
+}
+
+/// Whether each pooled token is still in rotation, and its latest budget.
+async fn token_health(Extension(gapi): Extension<Arc<GithubAPI>>) -> Json<Vec<TokenHealth>> {
+    Json(gapi.token_health())
+}
+
+#[derive(Debug, Deserialize)]
+struct PurgeParams {
+    key: Option<String>,
+    prefix: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct PurgeResult {
+    purged: usize,
+}
+
+/// Drops cached responses: a single `key` (a request path and query), every
+/// key under `prefix`, or everything when neither is given.
+async fn purge_cache(
+    Query(params): Query<PurgeParams>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Json<PurgeResult> {
+    let purged = cache.purge(params.key.as_deref(), params.prefix.as_deref());
+    Json(PurgeResult { purged })
+}
+
+pub fn app(gapi: GithubAPI, cache: ServiceCache) -> Router {
+    Router::new()
+        .route("/", get(handler))
+        .route("/repos/:owner/:repo", get(repository_details))
+        .route("/orgs/:org/repos", get(org_repositories))
+        .route("/rate_limit", get(rate_limit))
+        .route("/admin/cache", delete(purge_cache))
+        .route("/admin/tokens", get(token_health))
+        .merge(actions::routes())
+        .merge(branches::routes())
+        .merge(issues::routes())
+        .merge(commits::routes())
+        .merge(contents::routes())
+        .merge(git::routes())
+        .merge(pulls::routes())
+        .merge(releases::routes())
+        .merge(secrets::routes())
+        .merge(tags::routes())
+        .layer(Extension(Arc::new(gapi)))
+        .layer(Extension(Arc::new(cache)))
+        .layer(TraceLayer::new_for_http())
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::app;
+    use crate::cache::ServiceCache;
+    use crate::github::GithubAPI;
+    use axum::Server;
+    use std::net::{SocketAddr, TcpListener};
+    use std::time::Duration;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static MOCK_BODY: &str = include_str!("../github/fixtures/repository.json");
+
+    fn test_cache() -> ServiceCache {
+        ServiceCache::new(Duration::from_secs(60), Duration::from_secs(60))
+    }
+
+    pub(crate) fn spawn_app(gapi: GithubAPI) -> SocketAddr {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = Server::from_tcp(listener)
+            .unwrap()
+            .serve(app(gapi, test_cache()).into_make_service());
+        tokio::spawn(server);
+        addr
+    }
+
+    #[tokio::test]
+    pub async fn test_repository_details_route() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!("http://{}/repos/tarkalabs/ssh-signer", addr))
+            .await
+            .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("tarkalabs/ssh-signer", body["full_name"]);
+        assert_eq!("a test repo", body["description"]);
+    }
+
+    #[tokio::test]
+    pub async fn test_repository_details_route_is_cached() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(MOCK_BODY)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .expect(2)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let client = reqwest::Client::new();
+        let url = format!("http://{}/repos/tarkalabs/ssh-signer", addr);
+        let x_cache = |resp: &reqwest::Response| resp.headers()["x-cache"].clone();
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!("MISS", x_cache(&resp));
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!("HIT", x_cache(&resp));
+        let resp = client
+            .delete(format!(
+                "http://{}/admin/cache?prefix=/repos/tarkalabs/",
+                addr
+            ))
+            .send()
+            .await
+            .unwrap();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(1, body["purged"]);
+        let resp = client.get(&url).send().await.unwrap();
+        assert_eq!("MISS", x_cache(&resp));
+    }
+
+    #[tokio::test]
+    pub async fn test_rate_limit_route() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/rate_limit"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_json(serde_json::json!({ "resources": {} }))
+                    .insert_header("X-RateLimit-Limit", "5000")
+                    .insert_header("X-RateLimit-Remaining", "4999")
+                    .insert_header("X-RateLimit-Reset", "1700000000"),
+            )
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!("http://{}/rate_limit", addr))
+            .await
+            .unwrap();
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(4999, body["remaining"]);
+        assert_eq!(1700000000, body["reset_at"]);
+    }
+
+    #[tokio::test]
+    pub async fn test_repository_details_route_not_found() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Not Found",
+                "documentation_url": "https://docs.github.com/rest/repos/repos#get-a-repository"
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!("http://{}/repos/tarkalabs/missing", addr))
+            .await
+            .unwrap();
+        assert_eq!(404, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("not_found", body["error"]["code"]);
+        assert_eq!("Not Found", body["error"]["message"]);
+        assert_eq!(404, body["error"]["upstream_status"]);
+    }
+
+    #[tokio::test]
+    pub async fn test_token_health_route() {
+        let server = MockServer::start().await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!("http://{}/admin/tokens", addr))
+            .await
+            .unwrap();
+        let body = resp.text().await.unwrap();
+        assert!(!body.contains("test-token"));
+        let body: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(0, body[0]["index"]);
+        assert_eq!(true, body[0]["healthy"]);
+    }
+}
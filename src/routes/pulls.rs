@@ -0,0 +1,233 @@
+use super::ListParams;
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{
+    GithubAPI, MergeRequest, MergeResult, NewReview, NewReviewComment, PullRequest,
+    PullRequestFilter, ReviewerRequest,
+};
+use axum::{
+    extract::{Path, Query},
+    http::{StatusCode, Uri},
+    response::IntoResponse,
+    routing::{get, post, put},
+    Extension, Json, Router,
+};
+use std::sync::Arc;
+
+type PullPath = Path<(String, String, u64)>;
+
+/// Drops every cached pull request response of the repository after a
+/// write.
+fn purge_pulls(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/pulls", owner, repo)));
+}
+
+async fn list_pulls(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(filter): Query<PullRequestFilter>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_pull_requests(&owner, &repo, &filter, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn get_pull(
+    uri: Uri,
+    Path((owner, repo, number)): PullPath,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_pull_request(&owner, &repo, number).await?)
+        })
+        .await
+}
+
+async fn list_files(
+    uri: Uri,
+    Path((owner, repo, number)): PullPath,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_pull_request_files(&owner, &repo, number, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn list_reviews(
+    uri: Uri,
+    Path((owner, repo, number)): PullPath,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_reviews(&owner, &repo, number, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_review(
+    Path((owner, repo, number)): PullPath,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(review): Json<NewReview>,
+) -> Result<impl IntoResponse, AppError> {
+    let review = gapi.create_review(&owner, &repo, number, &review).await?;
+    purge_pulls(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(review)))
+}
+
+async fn list_review_comments(
+    uri: Uri,
+    Path((owner, repo, number)): PullPath,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_review_comments(&owner, &repo, number, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_review_comment(
+    Path((owner, repo, number)): PullPath,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(comment): Json<NewReviewComment>,
+) -> Result<impl IntoResponse, AppError> {
+    let comment = gapi
+        .create_review_comment(&owner, &repo, number, &comment)
+        .await?;
+    purge_pulls(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(comment)))
+}
+
+async fn request_reviewers(
+    Path((owner, repo, number)): PullPath,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(request): Json<ReviewerRequest>,
+) -> Result<(StatusCode, Json<PullRequest>), AppError> {
+    let pull = gapi
+        .request_reviewers(&owner, &repo, number, &request)
+        .await?;
+    purge_pulls(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(pull)))
+}
+
+/// Merges the pull request. A stale `sha` guard answers 409 and an
+/// unmergeable pull request 405, as GitHub does.
+async fn merge(
+    Path((owner, repo, number)): PullPath,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(merge): Json<MergeRequest>,
+) -> Result<Json<MergeResult>, AppError> {
+    let result = gapi
+        .merge_pull_request(&owner, &repo, number, &merge)
+        .await?;
+    purge_pulls(&cache, &owner, &repo);
+    Ok(Json(result))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route("/repos/:owner/:repo/pulls", get(list_pulls))
+        .route("/repos/:owner/:repo/pulls/:number", get(get_pull))
+        .route("/repos/:owner/:repo/pulls/:number/files", get(list_files))
+        .route(
+            "/repos/:owner/:repo/pulls/:number/reviews",
+            get(list_reviews).post(create_review),
+        )
+        .route(
+            "/repos/:owner/:repo/pulls/:number/comments",
+            get(list_review_comments).post(create_review_comment),
+        )
+        .route(
+            "/repos/:owner/:repo/pulls/:number/requested_reviewers",
+            post(request_reviewers),
+        )
+        .route("/repos/:owner/:repo/pulls/:number/merge", put(merge))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static REVIEW: &str = include_str!("../github/fixtures/review.json");
+
+    #[tokio::test]
+    async fn test_pull_request_routes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/18/reviews"))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(format!("[{}]", REVIEW))
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path("/repos/tarkalabs/ssh-signer/pulls/18/merge"))
+            .respond_with(ResponseTemplate::new(409).set_body_json(serde_json::json!({
+                "message": "Head branch was modified. Review and try the merge again."
+            })))
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let client = reqwest::Client::new();
+        let reviews: serde_json::Value = client
+            .get(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/pulls/18/reviews",
+                addr
+            ))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        assert_eq!("APPROVED", reviews[0]["state"]);
+        let resp = client
+            .put(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/pulls/18/merge",
+                addr
+            ))
+            .json(&serde_json::json!({ "sha": "stale", "merge_method": "rebase" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(409, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("conflict", body["error"]["code"]);
+        assert_eq!(409, body["error"]["upstream_status"]);
+    }
+}
@@ -0,0 +1,246 @@
+use super::{stream_response, ListParams};
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{AssetUpload, GithubAPI, NewRelease, Release, ReleaseAsset, ReleaseUpdate};
+use axum::{
+    extract::{BodyStream, Path, Query},
+    http::{
+        header::{CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderMap, StatusCode, Uri,
+    },
+    response::Response,
+    routing::{get, patch, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+fn purge_releases(cache: &ServiceCache, owner: &str, repo: &str) {
+    cache.purge(None, Some(&format!("/repos/{}/{}/releases", owner, repo)));
+}
+
+async fn list_releases(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_releases(&owner, &repo, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_release(
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(release): Json<NewRelease>,
+) -> Result<(StatusCode, Json<Release>), AppError> {
+    let release = gapi.create_release(&owner, &repo, &release).await?;
+    purge_releases(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(release)))
+}
+
+async fn latest_release(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_latest_release(&owner, &repo).await?)
+        })
+        .await
+}
+
+async fn release_by_tag(
+    uri: Uri,
+    Path((owner, repo, tag)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            let tag = tag.trim_start_matches('/');
+            Ok(gapi.get_release_by_tag(&owner, &repo, tag).await?)
+        })
+        .await
+}
+
+async fn update_release(
+    Path((owner, repo, id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(update): Json<ReleaseUpdate>,
+) -> Result<Json<Release>, AppError> {
+    let release = gapi.update_release(&owner, &repo, id, &update).await?;
+    purge_releases(&cache, &owner, &repo);
+    Ok(Json(release))
+}
+
+async fn delete_release(
+    Path((owner, repo, id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.delete_release(&owner, &repo, id).await?;
+    purge_releases(&cache, &owner, &repo);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+#[derive(Debug, Deserialize)]
+struct AssetParams {
+    name: String,
+    label: Option<String>,
+}
+
+/// Uploads the request body as a release asset, passing it on to GitHub as
+/// it arrives. The request must carry a `Content-Length`.
+async fn upload_asset(
+    Path((owner, repo, id)): Path<(String, String, u64)>,
+    Query(params): Query<AssetParams>,
+    headers: HeaderMap,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    body: BodyStream,
+) -> Result<(StatusCode, Json<ReleaseAsset>), AppError> {
+    let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+    let content_length = header(CONTENT_LENGTH)
+        .and_then(|len| len.parse().ok())
+        .ok_or_else(|| AppError::BadRequest("Content-Length is required".into()))?;
+    let upload = AssetUpload {
+        name: params.name,
+        label: params.label,
+        content_type: header(CONTENT_TYPE)
+            .unwrap_or("application/octet-stream")
+            .to_string(),
+        content_length,
+    };
+    let asset = gapi
+        .upload_release_asset(&owner, &repo, id, &upload, reqwest::Body::wrap_stream(body))
+        .await?;
+    purge_releases(&cache, &owner, &repo);
+    Ok((StatusCode::CREATED, Json(asset)))
+}
+
+async fn download_asset(
+    Path((owner, repo, asset_id)): Path<(String, String, u64)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+) -> Result<Response, AppError> {
+    let stream = gapi.download_release_asset(&owner, &repo, asset_id).await?;
+    let content_type = stream
+        .content_type
+        .clone()
+        .unwrap_or_else(|| "application/octet-stream".into());
+    Ok(stream_response(&content_type, stream))
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route(
+            "/repos/:owner/:repo/releases",
+            get(list_releases).post(create_release),
+        )
+        .route("/repos/:owner/:repo/releases/latest", get(latest_release))
+        .route(
+            "/repos/:owner/:repo/releases/tags/*tag",
+            get(release_by_tag),
+        )
+        .route(
+            "/repos/:owner/:repo/releases/:id",
+            patch(update_release).delete(delete_release),
+        )
+        .route(
+            "/repos/:owner/:repo/releases/:id/assets",
+            post(upload_asset),
+        )
+        .route(
+            "/repos/:owner/:repo/releases/assets/:asset_id",
+            get(download_asset),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::{ClientOptions, Credentials, GithubAPI};
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{body_bytes, header, method, path, query_param},
+        Mock, MockServer, ResponseTemplate,
+    };
+    static RELEASE: &str = include_str!("../github/fixtures/release.json");
+
+    #[tokio::test]
+    async fn test_upload_asset_route() {
+        let server = MockServer::start().await;
+        let asset =
+            serde_json::from_str::<serde_json::Value>(RELEASE).unwrap()["assets"][0].clone();
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/releases/7102447/assets"))
+            .and(query_param("name", "checksums.txt"))
+            .and(header("Content-Type", "text/plain"))
+            .and(body_bytes(b"abc123  ssh-signer\n".to_vec()))
+            .respond_with(ResponseTemplate::new(201).set_body_json(asset))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let options = ClientOptions {
+            upload_url: Some(server.uri()),
+            ..ClientOptions::default()
+        };
+        let gapi = GithubAPI::with_options(
+            Credentials::Token("test-token".into()),
+            Some(server.uri()),
+            options,
+        )
+        .unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .post(format!(
+                "http://{}/repos/tarkalabs/ssh-signer/releases/7102447/assets?name=checksums.txt",
+                addr
+            ))
+            .header("Content-Type", "text/plain")
+            .body("abc123  ssh-signer\n")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(201, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(1083624, body["id"]);
+    }
+
+    #[tokio::test]
+    async fn test_release_by_slashed_tag_route() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/releases/tags/release/1.0",
+            ))
+            .respond_with(
+                ResponseTemplate::new(200)
+                    .set_body_string(RELEASE)
+                    .insert_header("Content-Type", "application/json"),
+            )
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/releases/tags/release/1.0",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!(7102447, body["id"]);
+    }
+}
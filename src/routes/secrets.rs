@@ -0,0 +1,370 @@
+use super::ListParams;
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{GithubAPI, NewVariable, SecretOutcome, SecretScope, SecretValue, SecretWrite};
+use axum::{
+    extract::{Path, Query},
+    http::{StatusCode, Uri},
+    routing::{get, post},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// The path parameters of every secret and variable route. Which of them
+/// are set decides the scope.
+#[derive(Debug, Deserialize)]
+struct ScopePath {
+    owner: Option<String>,
+    repo: Option<String>,
+    org: Option<String>,
+    environment: Option<String>,
+    name: Option<String>,
+}
+
+impl ScopePath {
+    fn scope(&self) -> SecretScope<'_> {
+        match (&self.org, &self.owner, &self.repo, &self.environment) {
+            (Some(org), ..) => SecretScope::Org(org),
+            (_, Some(owner), Some(repo), Some(environment)) => SecretScope::Environment {
+                owner,
+                repo,
+                environment,
+            },
+            (_, Some(owner), Some(repo), None) => SecretScope::Repo { owner, repo },
+            _ => unreachable!("routes always name an org or a repository"),
+        }
+    }
+
+    fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or_default()
+    }
+
+    /// Drops every cached listing and lookup of the scope's `kind`.
+    fn purge(&self, cache: &ServiceCache, kind: &str) {
+        cache.purge(None, Some(&format!("/{}", self.scope().path(kind))));
+    }
+}
+
+async fn list_secrets(
+    uri: Uri,
+    Path(path): Path<ScopePath>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_secrets(path.scope(), params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn get_secret(
+    uri: Uri,
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_secret(path.scope(), path.name()).await?)
+        })
+        .await
+}
+
+/// Responds like GitHub: 201 for a new secret, 204 for a replaced value.
+async fn set_secret(
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(secret): Json<SecretValue>,
+) -> Result<StatusCode, AppError> {
+    let write = gapi.set_secret(path.scope(), path.name(), &secret).await?;
+    path.purge(&cache, "secrets");
+    Ok(match write {
+        SecretWrite::Created => StatusCode::CREATED,
+        SecretWrite::Updated => StatusCode::NO_CONTENT,
+    })
+}
+
+async fn delete_secret(
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.delete_secret(path.scope(), path.name()).await?;
+    path.purge(&cache, "secrets");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// A repository secret to store on several repositories. Organization-only
+/// fields such as `visibility` are rejected rather than silently dropped.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BulkSecret {
+    repositories: Vec<String>,
+    value: String,
+}
+
+/// Stores the same repository secret on many of the organization's
+/// repositories and reports the outcome for each, succeeding even when some
+/// of them fail.
+async fn bulk_set_secret(
+    Path((org, name)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(bulk): Json<BulkSecret>,
+) -> Result<Json<Vec<SecretOutcome>>, AppError> {
+    if bulk.repositories.is_empty() {
+        return Err(AppError::BadRequest(
+            "repositories must not be empty".into(),
+        ));
+    }
+    let outcomes = gapi
+        .set_secret_on_repos(&org, &bulk.repositories, &name, &bulk.value)
+        .await;
+    for repo in &bulk.repositories {
+        let scope = SecretScope::Repo { owner: &org, repo };
+        cache.purge(None, Some(&format!("/{}", scope.path("secrets"))));
+    }
+    Ok(Json(outcomes))
+}
+
+async fn list_variables(
+    uri: Uri,
+    Path(path): Path<ScopePath>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_variables(path.scope(), params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_variable(
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(variable): Json<NewVariable>,
+) -> Result<StatusCode, AppError> {
+    gapi.create_variable(path.scope(), &variable).await?;
+    path.purge(&cache, "variables");
+    Ok(StatusCode::CREATED)
+}
+
+async fn get_variable(
+    uri: Uri,
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi.get_variable(path.scope(), path.name()).await?)
+        })
+        .await
+}
+
+async fn update_variable(
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(variable): Json<NewVariable>,
+) -> Result<StatusCode, AppError> {
+    gapi.update_variable(path.scope(), path.name(), &variable)
+        .await?;
+    path.purge(&cache, "variables");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_variable(
+    Path(path): Path<ScopePath>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<StatusCode, AppError> {
+    gapi.delete_variable(path.scope(), path.name()).await?;
+    path.purge(&cache, "variables");
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// The secret and variable routes of one scope, rooted at `base`.
+fn scope_routes(router: Router, base: &str) -> Router {
+    router
+        .route(&format!("{}/secrets", base), get(list_secrets))
+        .route(
+            &format!("{}/secrets/:name", base),
+            get(get_secret).put(set_secret).delete(delete_secret),
+        )
+        .route(
+            &format!("{}/variables", base),
+            get(list_variables).post(create_variable),
+        )
+        .route(
+            &format!("{}/variables/:name", base),
+            get(get_variable)
+                .patch(update_variable)
+                .delete(delete_variable),
+        )
+}
+
+pub(super) fn routes() -> Router {
+    let router = [
+        "/repos/:owner/:repo/actions",
+        "/orgs/:org/actions",
+        "/repos/:owner/:repo/environments/:environment",
+    ]
+    .into_iter()
+    .fold(Router::new(), scope_routes);
+    router.route(
+        "/orgs/:org/actions/repo-secrets/:name",
+        post(bulk_set_secret),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use crypto_box::{aead::OsRng, SecretKey};
+    use wiremock::{
+        matchers::{method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_environment_variable_routes() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/environments/production/variables",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "total_count": 1,
+                "variables": [{
+                    "name": "REGION",
+                    "value": "eu-west-1",
+                    "created_at": "2022-08-05T11:06:31Z",
+                    "updated_at": "2022-08-05T11:06:31Z"
+                }]
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("PATCH"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/environments/production/variables/REGION",
+            ))
+            .respond_with(ResponseTemplate::new(204))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let base = format!(
+            "http://{}/repos/tarkalabs/ssh-signer/environments/production/variables",
+            addr
+        );
+        let body: serde_json::Value = reqwest::get(&base).await.unwrap().json().await.unwrap();
+        assert_eq!("eu-west-1", body[0]["value"]);
+        let resp = reqwest::Client::new()
+            .patch(format!("{}/REGION", base))
+            .json(&serde_json::json!({ "name": "REGION", "value": "eu-central-1" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(204, resp.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_bulk_secret_route_reports_each_repo() {
+        let server = MockServer::start().await;
+        let key = SecretKey::generate(&mut OsRng);
+        Mock::given(method("GET"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/secrets/public-key",
+            ))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "key_id": "568250167242549743",
+                "key": base64::encode(key.public_key().as_bytes())
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/repos/tarkalabs/ssh-signer/actions/secrets/DEPLOY_TOKEN",
+            ))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/archived/actions/secrets/public-key"))
+            .respond_with(ResponseTemplate::new(404).set_body_json(serde_json::json!({
+                "message": "Not Found"
+            })))
+            .mount(&server)
+            .await;
+        Mock::given(method("PUT"))
+            .and(path(
+                "/repos/tarkalabs/archived/actions/secrets/DEPLOY_TOKEN",
+            ))
+            .respond_with(ResponseTemplate::new(201))
+            .expect(0)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let url = format!(
+            "http://{}/orgs/tarkalabs/actions/repo-secrets/DEPLOY_TOKEN",
+            addr
+        );
+        let client = reqwest::Client::new();
+        let resp = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "repositories": ["ssh-signer", "archived"],
+                "value": "hunter2"
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(200, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("tarkalabs/ssh-signer", body[0]["repository"]);
+        assert_eq!("created", body[0]["result"]);
+        assert!(body[0].get("error").is_none());
+        assert_eq!("tarkalabs/archived", body[1]["repository"]);
+        assert!(body[1].get("result").is_none());
+        assert!(body[1]["error"].is_string());
+
+        let resp = client
+            .post(&url)
+            .json(&serde_json::json!({ "repositories": [], "value": "hunter2" }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(400, resp.status().as_u16());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("bad_request", body["error"]["code"]);
+
+        let resp = client
+            .post(&url)
+            .json(&serde_json::json!({
+                "repositories": ["ssh-signer"],
+                "value": "hunter2",
+                "visibility": "all"
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(422, resp.status().as_u16());
+    }
+}
@@ -0,0 +1,148 @@
+use super::ListParams;
+use crate::cache::{CachedJson, ServiceCache};
+use crate::error::AppError;
+use crate::github::{AnnotatedTag, GithubAPI, NewTag};
+use axum::{
+    extract::{Path, Query},
+    http::{StatusCode, Uri},
+    routing::get,
+    Extension, Json, Router,
+};
+use std::sync::Arc;
+
+async fn list_tags(
+    uri: Uri,
+    Path((owner, repo)): Path<(String, String)>,
+    Query(params): Query<ListParams>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            Ok(gapi
+                .list_tags(&owner, &repo, params.per_page(), params.limit())
+                .await?)
+        })
+        .await
+}
+
+async fn create_tag(
+    Path((owner, repo)): Path<(String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+    Json(tag): Json<NewTag>,
+) -> Result<(StatusCode, Json<AnnotatedTag>), AppError> {
+    let tag = gapi.create_annotated_tag(&owner, &repo, &tag).await?;
+    cache.purge(None, Some(&format!("/repos/{}/{}/tags", owner, repo)));
+    Ok((StatusCode::CREATED, Json(tag)))
+}
+
+/// The commit a tag points at, whether it is lightweight or annotated. Tag
+/// names may contain slashes.
+async fn resolve_tag(
+    uri: Uri,
+    Path((owner, repo, tag)): Path<(String, String, String)>,
+    Extension(gapi): Extension<Arc<GithubAPI>>,
+    Extension(cache): Extension<Arc<ServiceCache>>,
+) -> Result<CachedJson, AppError> {
+    cache
+        .get_or_fetch(uri.to_string(), move || async move {
+            let tag = tag.trim_start_matches('/');
+            Ok(gapi.resolve_tag(&owner, &repo, tag).await?)
+        })
+        .await
+}
+
+pub(super) fn routes() -> Router {
+    Router::new()
+        .route("/repos/:owner/:repo/tags", get(list_tags).post(create_tag))
+        .route("/repos/:owner/:repo/tags/*tag", get(resolve_tag))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::github::GithubAPI;
+    use crate::routes::tests::spawn_app;
+    use wiremock::{
+        matchers::{body_json, method, path},
+        Mock, MockServer, ResponseTemplate,
+    };
+
+    #[tokio::test]
+    async fn test_create_tag_route_creates_ref() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/tags"))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "sha": "940bd336248efae0f9ee5bc7b2d5c985887b16ac",
+                "node_id": "TA_kwDOEaeHgdoAKDk0MGJkMzM2MjQ4ZWZhZTBmOWVlNWJjN2Iy",
+                "tag": "v0.3.0",
+                "message": "Release v0.3.0\n",
+                "tagger": {
+                    "name": "release-bot",
+                    "email": "release-bot@tarkalabs.com",
+                    "date": "2022-09-01T09:10:00Z"
+                },
+                "object": { "sha": "c3d0be41ecbe669545ee3e94d31ed9a4bc91ee3c", "type": "commit" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/refs"))
+            .and(body_json(serde_json::json!({
+                "ref": "refs/tags/v0.3.0",
+                "sha": "940bd336248efae0f9ee5bc7b2d5c985887b16ac"
+            })))
+            .respond_with(ResponseTemplate::new(201).set_body_json(serde_json::json!({
+                "ref": "refs/tags/v0.3.0",
+                "node_id": "MDM6UmVmcmVmcy90YWdzL3YwLjMuMA==",
+                "object": { "sha": "940bd336248efae0f9ee5bc7b2d5c985887b16ac", "type": "tag" }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::Client::new()
+            .post(format!("http://{}/repos/tarkalabs/ssh-signer/tags", addr))
+            .json(&serde_json::json!({
+                "tag": "v0.3.0",
+                "message": "Release v0.3.0\n",
+                "object": "c3d0be41ecbe669545ee3e94d31ed9a4bc91ee3c"
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(201, resp.status().as_u16());
+    }
+
+    #[tokio::test]
+    async fn test_resolve_slashed_tag_route() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/repos/tarkalabs/ssh-signer/git/ref/tags/release/1.0"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!({
+                "ref": "refs/tags/release/1.0",
+                "node_id": "MDM6UmVmcmVmcy90YWdzL3JlbGVhc2UvMS4w",
+                "object": {
+                    "sha": "c3d0be41ecbe669545ee3e94d31ed9a4bc91ee3c",
+                    "type": "commit"
+                }
+            })))
+            .expect(1)
+            .mount(&server)
+            .await;
+        let gapi = GithubAPI::new("test-token".into(), Some(server.uri())).unwrap();
+        let addr = spawn_app(gapi);
+        let resp = reqwest::get(format!(
+            "http://{}/repos/tarkalabs/ssh-signer/tags/release/1.0",
+            addr
+        ))
+        .await
+        .unwrap();
+        assert!(resp.status().is_success());
+        let body: serde_json::Value = resp.json().await.unwrap();
+        assert_eq!("release/1.0", body["name"]);
+    }
+}